@@ -0,0 +1,85 @@
+//! Measures cost of pin lookups that `Snarl::show` performs every frame.
+//!
+//! Run with `cargo bench --bench wires`.
+//! Time per pin should stay roughly constant as the graph grows.
+
+use std::{hint::black_box, time::Instant};
+
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+
+const PINS: usize = 4;
+const WIRES_PER_NODE: usize = 3;
+const ROUNDS: u32 = 10;
+
+/// Builds graph with `nodes` nodes where each node feeds a few pseudo-random nodes.
+fn build(nodes: usize) -> (Snarl<()>, Vec<NodeId>) {
+    let mut snarl = Snarl::new();
+    let ids = (0..nodes)
+        .map(|idx| {
+            #[allow(clippy::cast_precision_loss)]
+            let pos = egui::pos2(idx as f32, 0.0);
+            snarl.insert_node(pos, ())
+        })
+        .collect::<Vec<_>>();
+
+    let mut seed = 0x2545_f491_u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        #[allow(clippy::cast_possible_truncation)]
+        let value = seed as usize;
+        value
+    };
+
+    for &node in &ids {
+        for _ in 0..WIRES_PER_NODE {
+            let from = OutPinId {
+                node,
+                output: next() % PINS,
+            };
+            let to = InPinId {
+                node: ids[next() % nodes],
+                input: next() % PINS,
+            };
+            snarl.connect(from, to);
+        }
+    }
+
+    (snarl, ids)
+}
+
+/// Collects remotes of every pin of every node, as rendering does.
+fn lookup_all_pins(snarl: &Snarl<()>, ids: &[NodeId]) -> usize {
+    let mut remotes = 0;
+    for &node in ids {
+        for idx in 0..PINS {
+            remotes += snarl.in_pin(InPinId { node, input: idx }).remotes.len();
+            remotes += snarl.out_pin(OutPinId { node, output: idx }).remotes.len();
+        }
+    }
+    remotes
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>8} {:>14} {:>12}",
+        "nodes", "wires", "frame", "per pin"
+    );
+
+    for nodes in [250, 500, 1000, 2000, 4000, 8000] {
+        let (snarl, ids) = build(nodes);
+        let wires = snarl.wires().count();
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(lookup_all_pins(black_box(&snarl), &ids));
+        }
+        let frame = start.elapsed() / ROUNDS;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let per_pin = frame / (nodes * PINS * 2) as u32;
+
+        println!("{nodes:>8} {wires:>8} {frame:>14.2?} {per_pin:>12.2?}");
+    }
+}
@@ -1,9 +1,14 @@
 use std::collections::HashMap;
 
 use eframe::{App, CreationContext};
-use egui::{epaint::Shadow, Color32, Ui};
+use egui::{epaint::Shadow, Color32, Key, Ui};
 use egui_snarl::{
-    ui::{PinInfo, SnarlStyle, SnarlViewer},
+    eval::{Evaluator, NodeEval},
+    layout::{ForceLayout, LayeredLayout},
+    ui::{
+        Effects, NewWires, PinInfo, SnarlStyle, SnarlViewer, SnarlViewport, WireFlow, WireInfo,
+        WireStyle,
+    },
     InPin, InPinId, NodeId, OutPin, Snarl,
 };
 
@@ -34,32 +39,37 @@ enum DemoNode {
 }
 
 impl DemoNode {
-    fn number_out(&self) -> f64 {
+    fn inputs(&self) -> usize {
         match self {
-            DemoNode::Number(value) => *value,
-            DemoNode::ExprNode(expr_node) => expr_node.eval(),
-            _ => unreachable!(),
+            DemoNode::Sink => 1,
+            DemoNode::Number(_) => 0,
+            DemoNode::String(_) => 0,
+            DemoNode::ShowImage(_) => 1,
+            DemoNode::ExprNode(expr_node) => 1 + expr_node.bindings.len(),
         }
     }
 
-    fn number_in(&mut self, idx: usize) -> &mut f64 {
+    fn outputs(&self) -> usize {
         match self {
-            DemoNode::ExprNode(expr_node) => &mut expr_node.values[idx - 1],
-            _ => unreachable!(),
+            DemoNode::Sink => 0,
+            DemoNode::Number(_) => 1,
+            DemoNode::String(_) => 1,
+            DemoNode::ShowImage(_) => 1,
+            DemoNode::ExprNode(_) => 1,
         }
     }
 
-    fn label_in(&mut self, idx: usize) -> &str {
+    fn number_in(&mut self, idx: usize) -> &mut f64 {
         match self {
-            DemoNode::ShowImage(_) if idx == 0 => "URL",
-            DemoNode::ExprNode(expr_node) => &expr_node.bindings[idx - 1],
+            DemoNode::ExprNode(expr_node) => &mut expr_node.values[idx - 1],
             _ => unreachable!(),
         }
     }
 
-    fn string_out(&self) -> &str {
+    fn label_in(&self, idx: usize) -> &str {
         match self {
-            DemoNode::String(value) => &value,
+            DemoNode::ShowImage(_) if idx == 0 => "URL",
+            DemoNode::ExprNode(expr_node) => &expr_node.bindings[idx - 1],
             _ => unreachable!(),
         }
     }
@@ -80,49 +90,80 @@ impl DemoNode {
     }
 }
 
-struct DemoViewer;
+/// Value passed along wires.
+#[derive(Clone)]
+enum DemoValue {
+    Number(f64),
+    String(String),
+    Image(String),
+}
+
+struct DemoEval;
+
+impl NodeEval<DemoNode> for DemoEval {
+    type Value = DemoValue;
+
+    fn inputs(&mut self, node: &DemoNode) -> usize {
+        node.inputs()
+    }
+
+    fn evaluate(&mut self, node: &DemoNode, inputs: &[Vec<DemoValue>]) -> Vec<DemoValue> {
+        match node {
+            DemoNode::Sink => Vec::new(),
+            DemoNode::Number(value) => vec![DemoValue::Number(*value)],
+            DemoNode::String(value) => vec![DemoValue::String(value.clone())],
+            DemoNode::ShowImage(uri) => {
+                let uri = match inputs[0].first() {
+                    Some(DemoValue::String(uri)) => uri.clone(),
+                    _ => uri.clone(),
+                };
+                vec![DemoValue::Image(uri)]
+            }
+            DemoNode::ExprNode(expr_node) => {
+                // Wired inputs override values edited in the node.
+                let args = expr_node
+                    .values
+                    .iter()
+                    .zip(&inputs[1..])
+                    .map(|(&value, input)| match input.first() {
+                        Some(DemoValue::Number(value)) => *value,
+                        _ => value,
+                    })
+                    .collect::<Vec<_>>();
+
+                vec![DemoValue::Number(expr_node.eval(&args))]
+            }
+        }
+    }
+}
+
+struct DemoViewer<'a> {
+    evaluator: &'a mut Evaluator<DemoValue>,
+}
 
-impl SnarlViewer<DemoNode> for DemoViewer {
+impl SnarlViewer<DemoNode> for DemoViewer<'_> {
     #[inline]
-    fn connect(&mut self, from: &OutPin, to: &InPin, snarl: &mut Snarl<DemoNode>) {
-        // Validate connection
-        match (&snarl[from.id.node], &snarl[to.id.node]) {
+    fn can_connect(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<DemoNode>) -> bool {
+        let compatible = match (&snarl[from.id.node], &snarl[to.id.node]) {
             (DemoNode::Sink, _) => {
                 unreachable!("Sink node has no outputs")
             }
-            (_, DemoNode::Sink) => {}
+            (_, DemoNode::Sink) => true,
             (_, DemoNode::Number(_)) => {
                 unreachable!("Number node has no inputs")
             }
             (_, DemoNode::String(_)) => {
                 unreachable!("String node has no inputs")
             }
-            (DemoNode::Number(_), DemoNode::ShowImage(_)) => {
-                return;
-            }
-            (DemoNode::ShowImage(_), DemoNode::ShowImage(_)) => {
-                return;
-            }
-            (DemoNode::String(_), DemoNode::ShowImage(_)) => {}
-            (DemoNode::ExprNode(_), DemoNode::ExprNode(_)) => {}
-            (DemoNode::Number(_), DemoNode::ExprNode(_)) => {}
-            (DemoNode::String(_), DemoNode::ExprNode(_)) if to.id.input == 0 => {}
-            (DemoNode::String(_), DemoNode::ExprNode(_)) => {
-                return;
-            }
-            (DemoNode::ShowImage(_), DemoNode::ExprNode(_)) => {
-                return;
-            }
-            (DemoNode::ExprNode(_), DemoNode::ShowImage(_)) => {
-                return;
-            }
-        }
-
-        for &remote in &to.remotes {
-            snarl.disconnect(remote, to.id);
-        }
+            (DemoNode::String(_), DemoNode::ShowImage(_)) => true,
+            (DemoNode::String(_), DemoNode::ExprNode(_)) => to.id.input == 0,
+            (DemoNode::Number(_) | DemoNode::ExprNode(_), DemoNode::ExprNode(_)) => to.id.input > 0,
+            (DemoNode::Number(_) | DemoNode::ExprNode(_), DemoNode::ShowImage(_)) => false,
+            (DemoNode::ShowImage(_), _) => false,
+        };
 
-        snarl.connect(from.id, to.id);
+        // Values are propagated along wires, loops would never settle.
+        compatible && !snarl.would_create_cycle(from.id, to.id)
     }
 
     fn title(&mut self, node: &DemoNode) -> String {
@@ -136,23 +177,11 @@ impl SnarlViewer<DemoNode> for DemoViewer {
     }
 
     fn inputs(&mut self, node: &DemoNode) -> usize {
-        match node {
-            DemoNode::Sink => 1,
-            DemoNode::Number(_) => 0,
-            DemoNode::String(_) => 0,
-            DemoNode::ShowImage(_) => 1,
-            DemoNode::ExprNode(expr_node) => 1 + expr_node.bindings.len(),
-        }
+        node.inputs()
     }
 
     fn outputs(&mut self, node: &DemoNode) -> usize {
-        match node {
-            DemoNode::Sink => 0,
-            DemoNode::Number(_) => 1,
-            DemoNode::String(_) => 1,
-            DemoNode::ShowImage(_) => 1,
-            DemoNode::ExprNode(_) => 1,
-        }
+        node.outputs()
     }
 
     fn show_input(
@@ -160,46 +189,34 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         pin: &InPin,
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
     ) -> PinInfo {
-        match snarl[pin.id.node] {
+        let pin_info = match snarl[pin.id.node] {
             DemoNode::Sink => {
                 assert_eq!(pin.id.input, 0, "Sink node has only one input");
 
-                match &*pin.remotes {
-                    [] => {
+                match self.evaluator.input(pin).next() {
+                    None => {
                         ui.label("None");
                         PinInfo::circle().with_fill(UNTYPED_COLOR)
                     }
-                    [remote] => match snarl[remote.node] {
-                        DemoNode::Sink => unreachable!("Sink node has no outputs"),
-                        DemoNode::Number(value) => {
-                            assert_eq!(remote.output, 0, "Number node has only one output");
-                            ui.label(format_float(value));
-                            PinInfo::square().with_fill(NUMBER_COLOR)
-                        }
-                        DemoNode::String(ref value) => {
-                            assert_eq!(remote.output, 0, "String node has only one output");
-                            ui.label(format!("{:?}", value));
-                            PinInfo::triangle().with_fill(STRING_COLOR)
-                        }
-                        DemoNode::ExprNode(ref expr) => {
-                            assert_eq!(remote.output, 0, "Expr node has only one output");
-                            ui.label(format_float(expr.eval()));
-                            PinInfo::square().with_fill(NUMBER_COLOR)
-                        }
-                        DemoNode::ShowImage(ref uri) => {
-                            assert_eq!(remote.output, 0, "ShowImage node has only one output");
-
-                            let image = egui::Image::new(uri)
-                                .fit_to_original_size(scale)
-                                .show_loading_spinner(true);
-                            ui.add(image);
+                    Some(DemoValue::Number(value)) => {
+                        ui.label(format_float(*value));
+                        PinInfo::square().with_fill(NUMBER_COLOR)
+                    }
+                    Some(DemoValue::String(value)) => {
+                        ui.label(format!("{:?}", value));
+                        PinInfo::triangle().with_fill(STRING_COLOR)
+                    }
+                    Some(DemoValue::Image(uri)) => {
+                        let image = egui::Image::new(uri)
+                            .fit_to_original_size(scale)
+                            .show_loading_spinner(true);
+                        ui.add(image);
 
-                            PinInfo::circle().with_fill(IMAGE_COLOR)
-                        }
-                    },
-                    _ => unreachable!("Sink input has only one wire"),
+                        PinInfo::circle().with_fill(IMAGE_COLOR)
+                    }
                 }
             }
             DemoNode::Number(_) => {
@@ -208,47 +225,64 @@ impl SnarlViewer<DemoNode> for DemoViewer {
             DemoNode::String(_) => {
                 unreachable!("String node has no inputs")
             }
-            DemoNode::ShowImage(_) => match &*pin.remotes {
+            DemoNode::ShowImage(ref uri) => match &*pin.remotes {
                 [] => {
-                    let input = snarl[pin.id.node].string_in();
-                    egui::TextEdit::singleline(input)
+                    let mut input = uri.clone();
+                    let r = egui::TextEdit::singleline(&mut input)
                         .clip_text(false)
                         .desired_width(0.0)
                         .margin(ui.spacing().item_spacing)
-                        .show(ui);
+                        .show(ui)
+                        .response;
+
+                    if r.changed() {
+                        self.evaluator.invalidate(pin.id.node);
+                        effects.edit_node(pin.id.node, move |node| *node.string_in() = input);
+                    }
                     PinInfo::triangle().with_fill(STRING_COLOR)
                 }
-                [remote] => {
-                    let new_value = snarl[remote.node].string_out().to_owned();
+                [_] => {
+                    let value = match self.evaluator.input(pin).next() {
+                        Some(DemoValue::String(value)) => value.as_str(),
+                        _ => "",
+                    };
 
-                    egui::TextEdit::singleline(&mut &*new_value)
+                    egui::TextEdit::singleline(&mut &*value)
                         .clip_text(false)
                         .desired_width(0.0)
                         .margin(ui.spacing().item_spacing)
                         .show(ui);
 
-                    let input = snarl[pin.id.node].string_in();
-                    *input = new_value;
-
                     PinInfo::triangle().with_fill(STRING_COLOR)
                 }
                 _ => unreachable!("Sink input has only one wire"),
             },
-            DemoNode::ExprNode(_) if pin.id.input == 0 => {
-                let changed = match &*pin.remotes {
+            DemoNode::ExprNode(ref expr_node) if pin.id.input == 0 => {
+                match &*pin.remotes {
                     [] => {
-                        let input = snarl[pin.id.node].string_in();
-                        let r = egui::TextEdit::singleline(input)
+                        let mut text = expr_node.text.clone();
+                        let r = egui::TextEdit::singleline(&mut text)
                             .clip_text(false)
                             .desired_width(0.0)
                             .margin(ui.spacing().item_spacing)
                             .show(ui)
                             .response;
 
-                        r.changed()
+                        if r.changed() {
+                            let node = pin.id.node;
+                            self.evaluator.invalidate(node);
+                            effects.closure(move |snarl| {
+                                // Record old expression in history.
+                                snarl.edit_node(node);
+                                update_expr(snarl, node, text);
+                            });
+                        }
                     }
-                    [remote] => {
-                        let new_string = snarl[remote.node].string_out().to_owned();
+                    [_] => {
+                        let new_string = match self.evaluator.input(pin).next() {
+                            Some(DemoValue::String(value)) => value.clone(),
+                            _ => String::new(),
+                        };
 
                         egui::TextEdit::singleline(&mut &*new_string)
                             .clip_text(false)
@@ -256,104 +290,52 @@ impl SnarlViewer<DemoNode> for DemoViewer {
                             .margin(ui.spacing().item_spacing)
                             .show(ui);
 
-                        let input = snarl[pin.id.node].string_in();
-                        if new_string != *input {
-                            *input = new_string;
-                            true
-                        } else {
-                            false
+                        if new_string != expr_node.text {
+                            // Expression defines node's pins, so it is stored in the node.
+                            let node = pin.id.node;
+                            self.evaluator.invalidate(node);
+                            effects.closure(move |snarl| update_expr(snarl, node, new_string));
                         }
                     }
                     _ => unreachable!("Expr pins has only one wire"),
-                };
-
-                if changed {
-                    let expr_node = snarl[pin.id.node].expr_node();
-
-                    match syn::parse_str(&expr_node.text) {
-                        Ok(expr) => {
-                            expr_node.expr = expr;
-
-                            let values = Iterator::zip(
-                                expr_node.bindings.iter().map(String::clone),
-                                expr_node.values.iter().copied(),
-                            )
-                            .collect::<HashMap<String, f64>>();
-
-                            let mut new_bindings = Vec::new();
-                            expr_node.expr.extend_bindings(&mut new_bindings);
-
-                            let old_bindings =
-                                std::mem::replace(&mut expr_node.bindings, new_bindings.clone());
-
-                            let new_values = new_bindings
-                                .iter()
-                                .map(|name| values.get(&**name).copied().unwrap_or(0.0))
-                                .collect::<Vec<_>>();
-
-                            expr_node.values = new_values;
-
-                            let inputs = (0..expr_node.bindings.len())
-                                .map(|idx| {
-                                    snarl.in_pin(InPinId {
-                                        node: pin.id.node,
-                                        input: idx,
-                                    })
-                                })
-                                .collect::<Vec<_>>();
-
-                            for (idx, name) in old_bindings.iter().enumerate() {
-                                let new_idx =
-                                    new_bindings.iter().position(|new_name| *new_name == *name);
-
-                                match new_idx {
-                                    None => {
-                                        snarl.drop_inputs(inputs[idx].id);
-                                    }
-                                    Some(new_idx) if new_idx != idx => {
-                                        let new_in_pin = InPinId {
-                                            node: pin.id.node,
-                                            input: new_idx,
-                                        };
-                                        for &remote in &inputs[idx].remotes {
-                                            snarl.disconnect(remote, inputs[idx].id);
-                                            snarl.connect(remote, new_in_pin);
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        Err(_) => {}
-                    }
                 }
                 PinInfo::triangle().with_fill(STRING_COLOR)
             }
             DemoNode::ExprNode(ref expr_node) => {
                 if pin.id.input <= expr_node.bindings.len() {
+                    let idx = pin.id.input;
+                    ui.label(snarl[pin.id.node].label_in(idx));
+
                     match &*pin.remotes {
                         [] => {
-                            let node = &mut snarl[pin.id.node];
-                            ui.label(node.label_in(pin.id.input));
-                            ui.add(egui::DragValue::new(node.number_in(pin.id.input)));
-                            PinInfo::square().with_fill(NUMBER_COLOR)
-                        }
-                        [remote] => {
-                            let new_value = snarl[remote.node].number_out();
-                            let node = &mut snarl[pin.id.node];
-                            ui.label(node.label_in(pin.id.input));
-                            ui.label(format_float(new_value));
-                            *node.number_in(pin.id.input) = new_value;
-                            PinInfo::square().with_fill(NUMBER_COLOR)
+                            let mut value = expr_node.values[idx - 1];
+                            if ui.add(egui::DragValue::new(&mut value)).changed() {
+                                self.evaluator.invalidate(pin.id.node);
+                                effects.edit_node(pin.id.node, move |node| {
+                                    *node.number_in(idx) = value;
+                                });
+                            }
                         }
+                        [_] => match self.evaluator.input(pin).next() {
+                            Some(DemoValue::Number(value)) => {
+                                ui.label(format_float(*value));
+                            }
+                            _ => {
+                                ui.label("None");
+                            }
+                        },
                         _ => unreachable!("Expr pins has only one wire"),
                     }
+                    PinInfo::square().with_fill(NUMBER_COLOR)
                 } else {
                     ui.label("Removed");
                     PinInfo::circle().with_fill(Color32::BLACK)
                 }
             }
-        }
+        };
+
+        // Each input accepts single wire.
+        pin_info.with_max_connections(1)
     }
 
     fn show_output(
@@ -361,30 +343,46 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         pin: &OutPin,
         ui: &mut Ui,
         _scale: f32,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
     ) -> PinInfo {
         match snarl[pin.id.node] {
             DemoNode::Sink => {
                 unreachable!("Sink node has no outputs")
             }
-            DemoNode::Number(ref mut value) => {
+            DemoNode::Number(value) => {
                 assert_eq!(pin.id.output, 0, "Number node has only one output");
-                ui.add(egui::DragValue::new(value));
+                let mut value = value;
+                if ui.add(egui::DragValue::new(&mut value)).changed() {
+                    // Record old value in history only when it actually changes.
+                    self.evaluator.invalidate(pin.id.node);
+                    effects.edit_node(pin.id.node, move |node| *node = DemoNode::Number(value));
+                }
                 PinInfo::square().with_fill(NUMBER_COLOR)
             }
-            DemoNode::String(ref mut value) => {
+            DemoNode::String(ref value) => {
                 assert_eq!(pin.id.output, 0, "String node has only one output");
-                let edit = egui::TextEdit::singleline(value)
+                let mut value = value.clone();
+                let edit = egui::TextEdit::singleline(&mut value)
                     .clip_text(false)
                     .desired_width(0.0)
                     .margin(ui.spacing().item_spacing);
-                ui.add(edit);
+                if ui.add(edit).changed() {
+                    self.evaluator.invalidate(pin.id.node);
+                    effects.edit_node(pin.id.node, move |node| *node = DemoNode::String(value));
+                }
                 PinInfo::triangle().with_fill(STRING_COLOR)
             }
-            DemoNode::ExprNode(ref expr_node) => {
-                let value = expr_node.eval();
+            DemoNode::ExprNode(_) => {
                 assert_eq!(pin.id.output, 0, "Expr node has only one output");
-                ui.label(format_float(value));
+                match self.evaluator.output(pin.id) {
+                    Some(DemoValue::Number(value)) => {
+                        ui.label(format_float(*value));
+                    }
+                    _ => {
+                        ui.label("None");
+                    }
+                }
                 PinInfo::square().with_fill(NUMBER_COLOR)
             }
             DemoNode::ShowImage(_) => {
@@ -398,7 +396,7 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         &mut self,
         pin: &InPin,
         _style: &egui::Style,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
     ) -> Color32 {
         match snarl[pin.id.node] {
             DemoNode::Sink => {
@@ -436,7 +434,7 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         &mut self,
         pin: &OutPin,
         _style: &egui::Style,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
     ) -> Color32 {
         match snarl[pin.id.node] {
             DemoNode::Sink => {
@@ -449,36 +447,152 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         }
     }
 
+    fn wire_info(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<DemoNode>) -> WireInfo {
+        // Images are passed by URI, dashes tell them apart from plain strings.
+        let info = match snarl[from.id.node] {
+            DemoNode::ShowImage(_) => WireInfo::default().with_dash(6.0, 4.0),
+            _ => WireInfo::default(),
+        };
+
+        // Values flow into sinks that display them.
+        match snarl[to.id.node] {
+            DemoNode::Sink => info.with_flow(WireFlow::default()),
+            _ => info,
+        }
+    }
+
+    fn has_wire_hover_ui(&mut self, _from: &OutPin, _to: &InPin, _snarl: &Snarl<DemoNode>) -> bool {
+        true
+    }
+
+    fn wire_hover_ui(
+        &mut self,
+        from: &OutPin,
+        _to: &InPin,
+        ui: &mut Ui,
+        _scale: f32,
+        _snarl: &Snarl<DemoNode>,
+        _effects: &mut Effects<DemoNode>,
+    ) {
+        // Show value carried by the wire.
+        match self.evaluator.output(from.id) {
+            None => ui.label("None"),
+            Some(DemoValue::Number(value)) => ui.label(format_float(*value)),
+            Some(DemoValue::String(value)) => ui.label(format!("{:?}", value)),
+            Some(DemoValue::Image(uri)) => ui.label(uri),
+        };
+    }
+
+    fn has_wire_menu(&mut self, _from: &OutPin, _to: &InPin, _snarl: &Snarl<DemoNode>) -> bool {
+        true
+    }
+
+    fn wire_menu(
+        &mut self,
+        from: &OutPin,
+        to: &InPin,
+        ui: &mut Ui,
+        _scale: f32,
+        _snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
+    ) {
+        if ui.button("Disconnect").clicked() {
+            effects.disconnect(from.id, to.id);
+            ui.close_menu();
+        }
+    }
+
     fn graph_menu(
         &mut self,
         pos: egui::Pos2,
         ui: &mut Ui,
         _scale: f32,
-        snarl: &mut Snarl<DemoNode>,
+        _snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
     ) {
         ui.label("Add node");
         if ui.button("Number").clicked() {
-            snarl.insert_node(pos, DemoNode::Number(0.0));
+            effects.insert_node(pos, DemoNode::Number(0.0));
             ui.close_menu();
         }
         if ui.button("Expr").clicked() {
-            snarl.insert_node(pos, DemoNode::ExprNode(ExprNode::new()));
+            effects.insert_node(pos, DemoNode::ExprNode(ExprNode::new()));
             ui.close_menu();
         }
         if ui.button("String").clicked() {
-            snarl.insert_node(pos, DemoNode::String("".to_owned()));
+            effects.insert_node(pos, DemoNode::String("".to_owned()));
             ui.close_menu();
         }
         if ui.button("Show image").clicked() {
-            snarl.insert_node(pos, DemoNode::ShowImage("".to_owned()));
+            effects.insert_node(pos, DemoNode::ShowImage("".to_owned()));
             ui.close_menu();
         }
         if ui.button("Sink").clicked() {
-            snarl.insert_node(pos, DemoNode::Sink);
+            effects.insert_node(pos, DemoNode::Sink);
             ui.close_menu();
         }
     }
 
+    fn has_dropped_wire_menu(&mut self, _src_pins: &NewWires, _snarl: &Snarl<DemoNode>) -> bool {
+        true
+    }
+
+    fn dropped_wire_menu(
+        &mut self,
+        pos: egui::Pos2,
+        src_pins: &NewWires,
+        ui: &mut Ui,
+        _scale: f32,
+        _snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
+    ) {
+        ui.label("Add connected node");
+        match src_pins {
+            NewWires::Out(_) => {
+                if ui.button("Expr").clicked() {
+                    effects.insert_connected_node(
+                        pos,
+                        DemoNode::ExprNode(ExprNode::new()),
+                        src_pins,
+                        0,
+                    );
+                }
+                if ui.button("Show image").clicked() {
+                    effects.insert_connected_node(
+                        pos,
+                        DemoNode::ShowImage("".to_owned()),
+                        src_pins,
+                        0,
+                    );
+                }
+                if ui.button("Sink").clicked() {
+                    effects.insert_connected_node(pos, DemoNode::Sink, src_pins, 0);
+                }
+            }
+            NewWires::In(_) => {
+                if ui.button("Number").clicked() {
+                    effects.insert_connected_node(pos, DemoNode::Number(0.0), src_pins, 0);
+                }
+                if ui.button("Expr").clicked() {
+                    effects.insert_connected_node(
+                        pos,
+                        DemoNode::ExprNode(ExprNode::new()),
+                        src_pins,
+                        0,
+                    );
+                }
+                if ui.button("String").clicked() {
+                    effects.insert_connected_node(
+                        pos,
+                        DemoNode::String("".to_owned()),
+                        src_pins,
+                        0,
+                    );
+                }
+            }
+        }
+    }
+
     fn node_menu(
         &mut self,
         node: NodeId,
@@ -486,15 +600,34 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         _outputs: &[OutPin],
         ui: &mut Ui,
         _scale: f32,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
+        effects: &mut Effects<DemoNode>,
     ) {
         ui.label("Node menu");
         if ui.button("Remove").clicked() {
-            snarl.remove_node(node);
+            effects.remove_node(node);
+            ui.close_menu();
+        }
+        if snarl.selected_nodes().len() > 1 && ui.button("Remove selected").clicked() {
+            for &node in snarl.selected_nodes() {
+                effects.remove_node(node);
+            }
             ui.close_menu();
         }
     }
 
+    fn copy_nodes(&mut self, nodes: &[NodeId], snarl: &Snarl<DemoNode>) -> Option<Snarl<DemoNode>> {
+        Some(snarl.copy_nodes(nodes))
+    }
+
+    fn clipboard_text(&mut self, clip: &Snarl<DemoNode>) -> Option<String> {
+        clip.to_clipboard_text()
+    }
+
+    fn parse_clipboard_text(&mut self, text: &str) -> Option<Snarl<DemoNode>> {
+        Snarl::from_clipboard_text(text)
+    }
+
     fn has_on_hover_popup(&mut self, _: &DemoNode) -> bool {
         true
     }
@@ -506,7 +639,8 @@ impl SnarlViewer<DemoNode> for DemoViewer {
         _outputs: &[OutPin],
         ui: &mut Ui,
         _scale: f32,
-        snarl: &mut Snarl<DemoNode>,
+        snarl: &Snarl<DemoNode>,
+        _effects: &mut Effects<DemoNode>,
     ) {
         match snarl[node] {
             DemoNode::Sink => {
@@ -539,15 +673,15 @@ struct ExprNode {
 impl ExprNode {
     fn new() -> Self {
         ExprNode {
-            text: format!("0"),
+            text: "0".to_string(),
             bindings: Vec::new(),
             values: Vec::new(),
             expr: Expr::Val(0.0),
         }
     }
 
-    fn eval(&self) -> f64 {
-        self.expr.eval(&self.bindings, &self.values)
+    fn eval(&self, args: &[f64]) -> f64 {
+        self.expr.eval(&self.bindings, args)
     }
 }
 
@@ -821,24 +955,39 @@ impl Expr {
 
 pub struct DemoApp {
     snarl: Snarl<DemoNode>,
+    evaluator: Evaluator<DemoValue>,
+    force_layout: ForceLayout,
+    force_layout_running: bool,
+    wire_style: WireStyle,
+
+    /// Id of the Snarl widget, known after it is shown.
+    snarl_id: egui::Id,
 }
 
 impl DemoApp {
     pub fn new(cx: &CreationContext) -> Self {
-        let snarl = match cx.storage {
+        let mut snarl: Snarl<DemoNode> = match cx.storage {
             None => Snarl::new(),
-            Some(storage) => {
-                let snarl = storage
-                    .get_string("snarl")
-                    .and_then(|snarl| serde_json::from_str(&snarl).ok())
-                    .unwrap_or_else(Snarl::new);
-
-                snarl
-            }
+            Some(storage) => storage
+                .get_string("snarl")
+                .and_then(|snarl| serde_json::from_str(&snarl).ok())
+                .unwrap_or_else(Snarl::new),
         };
         // let snarl = Snarl::new();
 
-        DemoApp { snarl }
+        // Saved graph may have wires to pins that nodes no longer have.
+        snarl.prune_wires(|node| (node.inputs(), node.outputs()));
+
+        snarl.enable_history(100);
+
+        DemoApp {
+            snarl,
+            evaluator: Evaluator::new(),
+            force_layout: ForceLayout::new(),
+            force_layout_running: false,
+            wire_style: WireStyle::Bezier,
+            snarl_id: egui::Id::NULL,
+        }
     }
 }
 
@@ -857,6 +1006,74 @@ impl App for DemoApp {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close)
                     }
                 });
+                ui.menu_button("Edit", |ui| {
+                    if ui
+                        .add_enabled(self.snarl.can_undo(), egui::Button::new("Undo"))
+                        .clicked()
+                    {
+                        self.snarl.undo();
+                        self.evaluator.invalidate_all();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(self.snarl.can_redo(), egui::Button::new("Redo"))
+                        .clicked()
+                    {
+                        self.snarl.redo();
+                        self.evaluator.invalidate_all();
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    let viewport = SnarlViewport::new(self.snarl_id);
+                    if ui.button("Fit all").clicked() {
+                        viewport.fit_all(ctx);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.snarl.selected_nodes().is_empty(),
+                            egui::Button::new("Fit selected"),
+                        )
+                        .clicked()
+                    {
+                        viewport.fit_nodes(ctx, self.snarl.selected_nodes().iter().copied());
+                        ui.close_menu();
+                    }
+                    if ui.button("Reset zoom").clicked() {
+                        viewport.set_scale(ctx, 1.0);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Arrange all").clicked() {
+                        LayeredLayout::new().apply(&mut self.snarl);
+                        viewport.fit_all(ctx);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.snarl.selected_nodes().is_empty(),
+                            egui::Button::new("Arrange selected"),
+                        )
+                        .clicked()
+                    {
+                        let selected = self.snarl.selected_nodes().to_vec();
+                        LayeredLayout::new().apply_to(&mut self.snarl, selected);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.force_layout_running, "Force layout")
+                        .changed()
+                    {
+                        self.force_layout.restart();
+                    }
+                    ui.separator();
+                    ui.label("Wires");
+                    ui.radio_value(&mut self.wire_style, WireStyle::Bezier, "Bezier");
+                    ui.radio_value(&mut self.wire_style, WireStyle::Orthogonal, "Orthogonal");
+                    ui.radio_value(&mut self.wire_style, WireStyle::Straight, "Straight");
+                    ui.radio_value(&mut self.wire_style, WireStyle::Stepped, "Stepped");
+                });
                 ui.add_space(16.0);
 
                 egui::widgets::global_dark_light_mode_switch(ui);
@@ -878,11 +1095,27 @@ impl App for DemoApp {
             let node_frame = egui::Frame::window(ui.style()).fill(node_color);
             let header_frame = node_frame.shadow(Shadow::NONE).fill(header_color);
 
-            self.snarl.show(
-                &mut DemoViewer,
+            // Undo and redo shortcuts may change any node.
+            if ui.input(|i| i.modifiers.command && (i.key_pressed(Key::Z) || i.key_pressed(Key::Y)))
+            {
+                self.evaluator.invalidate_all();
+            }
+
+            // Cycles are rejected when connecting, so evaluation can't fail.
+            let _ = self.evaluator.evaluate(&self.snarl, &mut DemoEval);
+
+            if self.force_layout_running && self.force_layout.step(&mut self.snarl, 5) {
+                ctx.request_repaint();
+            }
+
+            let response = self.snarl.show(
+                &mut DemoViewer {
+                    evaluator: &mut self.evaluator,
+                },
                 &SnarlStyle {
                     collapsible: true,
                     wire_frame_size: Some(100.0),
+                    wire_style: self.wire_style,
                     node_frame: Some(node_frame),
                     header_frame: Some(header_frame),
                     ..Default::default()
@@ -890,6 +1123,21 @@ impl App for DemoApp {
                 egui::Id::new("snarl"),
                 ui,
             );
+
+            self.snarl_id = response.id;
+
+            // Nodes placed by the user stay where they are.
+            for &node in &response.moved_nodes {
+                self.force_layout.pin(node);
+            }
+            if response.changed {
+                self.force_layout.restart();
+            }
+
+            // Double-clicked node is moved to the center of the view.
+            if let Some(node) = response.double_clicked_node {
+                SnarlViewport::new(response.id).center_node(ctx, node);
+            }
         });
     }
 
@@ -914,6 +1162,66 @@ fn main() -> eframe::Result<()> {
     )
 }
 
+/// Sets new expression text and rebinds input pins
+/// according to variables of the parsed expression.
+fn update_expr(snarl: &mut Snarl<DemoNode>, node: NodeId, text: String) {
+    let expr_node = snarl[node].expr_node();
+    expr_node.text = text;
+
+    let Ok(expr) = syn::parse_str(&expr_node.text) else {
+        return;
+    };
+    expr_node.expr = expr;
+
+    let values = Iterator::zip(
+        expr_node.bindings.iter().map(String::clone),
+        expr_node.values.iter().copied(),
+    )
+    .collect::<HashMap<String, f64>>();
+
+    let mut new_bindings = Vec::new();
+    expr_node.expr.extend_bindings(&mut new_bindings);
+
+    let old_bindings = std::mem::replace(&mut expr_node.bindings, new_bindings.clone());
+
+    let new_values = new_bindings
+        .iter()
+        .map(|name| values.get(&**name).copied().unwrap_or(0.0))
+        .collect::<Vec<_>>();
+
+    expr_node.values = new_values;
+
+    let inputs = (0..old_bindings.len())
+        .map(|idx| {
+            snarl.in_pin(InPinId {
+                node,
+                input: idx + 1,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (idx, name) in old_bindings.iter().enumerate() {
+        let new_idx = new_bindings.iter().position(|new_name| *new_name == *name);
+
+        match new_idx {
+            None => {
+                snarl.drop_inputs(inputs[idx].id);
+            }
+            Some(new_idx) if new_idx != idx => {
+                let new_in_pin = InPinId {
+                    node,
+                    input: new_idx + 1,
+                };
+                for &remote in &inputs[idx].remotes {
+                    snarl.disconnect(remote, inputs[idx].id);
+                    snarl.connect(remote, new_in_pin);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 fn format_float(v: f64) -> String {
     let v = (v * 1000.0).round() / 1000.0;
     format!("{}", v)
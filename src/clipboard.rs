@@ -0,0 +1,259 @@
+//! Copying and pasting of node subgraphs.
+//!
+//! Copied nodes are stored in a separate [`Snarl`] together with wires between them.
+//! Such subgraph can be pasted into any Snarl with fresh node identifiers.
+
+use egui::{
+    ahash::{HashMap, HashSet},
+    Pos2, Vec2,
+};
+
+use crate::{InPinId, Node, NodeId, NodeLayout, OutPinId, Snarl, Wire};
+
+impl<T> Snarl<T> {
+    /// Copies nodes into new Snarl together with wires between them.
+    /// Wires to nodes that are not copied are omitted.
+    ///
+    /// Nodes that do not exist are skipped.
+    /// Node positions are preserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<i32>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), 2);
+    /// let c = snarl.insert_node(egui::pos2(200.0, 0.0), 3);
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    /// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+    ///
+    /// let clip = snarl.copy_nodes(&[a, b]);
+    /// assert_eq!(clip.nodes().count(), 2);
+    /// assert_eq!(clip.wires().count(), 1);
+    /// ```
+    #[must_use]
+    pub fn copy_nodes(&self, nodes: &[NodeId]) -> Snarl<T>
+    where
+        T: Clone,
+    {
+        let mut clip = Snarl::new();
+        let mut ids = HashMap::default();
+
+        for &node in nodes {
+            let Some(entry) = self.nodes.get(node.index) else {
+                continue;
+            };
+            if entry.generation != node.generation {
+                continue;
+            }
+            ids.entry(node).or_insert_with(|| {
+                clip.push_node(entry.value.clone(), entry.pos, entry.open, entry.layout)
+            });
+        }
+
+        for &node in ids.keys() {
+            for wire in self.wires.node_out_wires(node) {
+                if let Some(new_wire) = remap_wire(wire, &ids) {
+                    clip.wires.insert(new_wire);
+                    clip.wires
+                        .set_reroutes(new_wire, self.wires.reroutes(&wire).to_vec());
+                }
+            }
+        }
+
+        clip
+    }
+
+    /// Removes nodes from the Snarl and returns them in new Snarl
+    /// together with wires between them.
+    ///
+    /// Nodes that do not exist are skipped.
+    /// If history is enabled, the removal is recorded as single step.
+    pub fn cut_nodes(&mut self, nodes: &[NodeId]) -> Snarl<T> {
+        let mut cut = Vec::new();
+        let mut cut_set = HashSet::default();
+        for &node in nodes {
+            if self.contains_node(node) && cut_set.insert(node) {
+                cut.push(node);
+            }
+        }
+
+        // Collect internal wires before nodes are removed.
+        let wires = cut
+            .iter()
+            .flat_map(|&node| self.wires.node_out_wires(node))
+            .filter(|wire| cut_set.contains(&wire.in_pin.node))
+            .map(|wire| (wire, self.wires.reroutes(&wire).to_vec()))
+            .collect::<Vec<_>>();
+
+        let mut clip = Snarl::new();
+        let mut ids = HashMap::default();
+
+        self.begin_history_group();
+        for node in cut {
+            let Node {
+                pos, open, layout, ..
+            } = *self.node(node);
+            let value = self.remove_node(node);
+            ids.insert(node, clip.push_node(value, pos, open, layout));
+        }
+        self.end_history_group();
+
+        for (wire, reroutes) in wires {
+            if let Some(wire) = remap_wire(wire, &ids) {
+                clip.wires.insert(wire);
+                clip.wires.set_reroutes(wire, reroutes);
+            }
+        }
+
+        clip
+    }
+
+    /// Inserts all nodes and wires of the `clip` into the Snarl.
+    /// Nodes receive fresh identifiers and are shifted so that
+    /// top-left corner of their bounding box is placed at `pos`.
+    ///
+    /// Pasted nodes become selected.
+    /// If history is enabled, the paste is recorded as single step.
+    ///
+    /// Returns identifiers of the pasted nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<i32>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), 2);
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    ///
+    /// let clip = snarl.copy_nodes(&[a, b]);
+    /// let pasted = snarl.paste(clip, egui::pos2(0.0, 200.0));
+    ///
+    /// assert_eq!(pasted.len(), 2);
+    /// assert_eq!(snarl.nodes().count(), 4);
+    /// assert_eq!(snarl.wires().count(), 2);
+    /// assert_eq!(snarl.selected_nodes(), pasted);
+    /// ```
+    pub fn paste(&mut self, clip: Snarl<T>, pos: Pos2) -> Vec<NodeId> {
+        let offset = clip.min_pos().map_or(Vec2::ZERO, |min| pos - min);
+        let mut ids = HashMap::default();
+        let mut pasted = Vec::new();
+
+        self.begin_history_group();
+        for (idx, node) in clip.nodes {
+            let old_id = node.id(idx);
+            let pos = node.pos + offset;
+            let id = if node.open {
+                self.insert_node(pos, node.value)
+            } else {
+                self.add_node_collapsed(pos, node.value)
+            };
+            // Keep measured size, so pasted nodes do not jump.
+            self.node_mut(id).layout = node.layout;
+            ids.insert(old_id, id);
+            pasted.push(id);
+        }
+        for wire in clip.wires.iter() {
+            if let Some(new_wire) = remap_wire(wire, &ids) {
+                self.connect(new_wire.out_pin, new_wire.in_pin);
+                let reroutes = clip.wires.reroutes(&wire);
+                if !reroutes.is_empty() {
+                    let reroutes = reroutes.iter().map(|&point| point + offset).collect();
+                    self.edit_reroutes(new_wire, |points| *points = reroutes);
+                }
+            }
+        }
+        self.end_history_group();
+
+        self.set_selection(pasted.iter().copied());
+        pasted
+    }
+
+    /// Duplicates nodes together with wires between them.
+    /// Copies are shifted by `offset` from the original nodes.
+    ///
+    /// Copies become selected.
+    /// Returns identifiers of the copies.
+    pub fn duplicate_nodes(&mut self, nodes: &[NodeId], offset: Vec2) -> Vec<NodeId>
+    where
+        T: Clone,
+    {
+        let clip = self.copy_nodes(nodes);
+        match clip.min_pos() {
+            None => Vec::new(),
+            Some(min) => self.paste(clip, min + offset),
+        }
+    }
+
+    /// Serializes the Snarl into text suitable for system clipboard.
+    ///
+    /// Returns `None` if node values fail to serialize.
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub fn to_clipboard_text(&self) -> Option<String>
+    where
+        T: serde::Serialize,
+    {
+        serde_json::to_string(self).ok()
+    }
+
+    /// Deserializes the Snarl from text produced by [`Snarl::to_clipboard_text`].
+    ///
+    /// Returns `None` if the text is not a valid Snarl.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::Snarl;
+    /// let mut snarl = Snarl::<i32>::new();
+    /// let node = snarl.insert_node(egui::pos2(0.0, 0.0), 42);
+    ///
+    /// let text = snarl.copy_nodes(&[node]).to_clipboard_text().unwrap();
+    /// let clip = Snarl::<i32>::from_clipboard_text(&text).unwrap();
+    ///
+    /// let pasted = snarl.paste(clip, egui::pos2(0.0, 100.0));
+    /// assert_eq!(snarl[pasted[0]], 42);
+    /// ```
+    #[cfg(feature = "serde")]
+    #[must_use]
+    pub fn from_clipboard_text(text: &str) -> Option<Self>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        serde_json::from_str(text).ok()
+    }
+
+    /// Returns top-left corner of the bounding box of node positions.
+    pub(crate) fn min_pos(&self) -> Option<Pos2> {
+        self.nodes
+            .iter()
+            .map(|(_, node)| node.pos)
+            .reduce(|a, b| a.min(b))
+    }
+
+    /// Adds node without recording it in history.
+    fn push_node(&mut self, value: T, pos: Pos2, open: bool, layout: Option<NodeLayout>) -> NodeId {
+        let id = self.alloc_node(value, pos, open);
+        self.node_mut(id).layout = layout;
+        self.draw_order.push(id);
+        id
+    }
+}
+
+/// Maps wire ends to new node identifiers.
+/// Returns `None` if either end is not mapped.
+fn remap_wire(wire: Wire, ids: &HashMap<NodeId, NodeId>) -> Option<Wire> {
+    Some(Wire {
+        out_pin: OutPinId {
+            node: *ids.get(&wire.out_pin.node)?,
+            output: wire.out_pin.output,
+        },
+        in_pin: InPinId {
+            node: *ids.get(&wire.in_pin.node)?,
+            input: wire.in_pin.input,
+        },
+    })
+}
@@ -0,0 +1,62 @@
+//! Errors of fallible [`Snarl`](crate::Snarl) operations.
+
+use std::fmt;
+
+use crate::{InPinId, NodeId, OutPinId};
+
+/// Error returned by fallible graph mutations like [`Snarl::try_connect`](crate::Snarl::try_connect).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnarlError {
+    /// Node does not exist in the Snarl.
+    UnknownNode(NodeId),
+
+    /// Node does not have the input pin.
+    UnknownInPin(InPinId),
+
+    /// Node does not have the output pin.
+    UnknownOutPin(OutPinId),
+
+    /// Pins are already connected.
+    DuplicateWire {
+        /// Output pin of the wire.
+        from: OutPinId,
+
+        /// Input pin of the wire.
+        to: InPinId,
+    },
+
+    /// Wire would connect node to itself.
+    SelfLoop {
+        /// Output pin of the wire.
+        from: OutPinId,
+
+        /// Input pin of the wire.
+        to: InPinId,
+    },
+}
+
+impl fmt::Display for SnarlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SnarlError::UnknownNode(node) => write!(f, "node {node} does not exist"),
+            SnarlError::UnknownInPin(pin) => {
+                write!(f, "node {} has no input pin {}", pin.node, pin.input)
+            }
+            SnarlError::UnknownOutPin(pin) => {
+                write!(f, "node {} has no output pin {}", pin.node, pin.output)
+            }
+            SnarlError::DuplicateWire { from, to } => write!(
+                f,
+                "output {} of node {} is already connected to input {} of node {}",
+                from.output, from.node, to.input, to.node
+            ),
+            SnarlError::SelfLoop { from, to } => write!(
+                f,
+                "wire from output {} to input {} would connect node {} to itself",
+                from.output, to.input, from.node
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnarlError {}
@@ -0,0 +1,204 @@
+//! Dataflow evaluation of the [`Snarl`].
+//!
+//! Nodes are evaluated in topological order - every node is evaluated
+//! after all nodes that feed its inputs.
+//! Values produced on output pins are cached in [`Evaluator`]
+//! and nodes are recomputed only when they, their wires
+//! or any node upstream of them change.
+//!
+//! Evaluation is opt-in. Implement [`NodeEval`] for node type
+//! and call [`Evaluator::evaluate`] whenever graph may have changed,
+//! for example once per frame before [`Snarl::show`].
+//! Evaluated values can be read from [`SnarlViewer`](crate::ui::SnarlViewer)
+//! methods to display them in pins.
+
+use egui::ahash::{HashMap, HashSet};
+
+use crate::{Cycle, InPin, NodeId, OutPinId, Snarl, Wire};
+
+/// Evaluates nodes of type `T`.
+pub trait NodeEval<T> {
+    /// Value passed along wires.
+    type Value: Clone;
+
+    /// Returns number of input pins of the node.
+    ///
+    /// Usually this is the same as [`SnarlViewer::inputs`](crate::ui::SnarlViewer::inputs).
+    fn inputs(&mut self, node: &T) -> usize;
+
+    /// Evaluates the node.
+    ///
+    /// `inputs` has an element for each input pin of the node,
+    /// containing values of all wires connected to the pin.
+    /// Returns values of node's output pins in order.
+    fn evaluate(&mut self, node: &T, inputs: &[Vec<Self::Value>]) -> Vec<Self::Value>;
+}
+
+/// Cache of values produced by nodes.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_snarl::{InPinId, OutPinId, Snarl, eval::{Evaluator, NodeEval}};
+/// /// Each node outputs its own value plus sum of its inputs.
+/// struct Sum;
+///
+/// impl NodeEval<i32> for Sum {
+///     type Value = i32;
+///
+///     fn inputs(&mut self, _node: &i32) -> usize {
+///         1
+///     }
+///
+///     fn evaluate(&mut self, node: &i32, inputs: &[Vec<i32>]) -> Vec<i32> {
+///         vec![node + inputs[0].iter().sum::<i32>()]
+///     }
+/// }
+///
+/// let mut snarl = Snarl::new();
+/// let a = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+/// let b = snarl.insert_node(egui::pos2(100.0, 0.0), 2);
+/// let c = snarl.insert_node(egui::pos2(200.0, 0.0), 3);
+/// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+///
+/// let mut evaluator = Evaluator::new();
+/// evaluator.evaluate(&snarl, &mut Sum).unwrap();
+/// assert_eq!(evaluator.output(OutPinId { node: b, output: 0 }), Some(&3));
+///
+/// // Only `b` depends on `a`.
+/// *snarl.get_node_mut(a).unwrap() = 10;
+/// evaluator.invalidate(a);
+/// assert_eq!(evaluator.evaluate(&snarl, &mut Sum).unwrap(), [a, b]);
+/// assert_eq!(evaluator.output(OutPinId { node: b, output: 0 }), Some(&12));
+///
+/// // New wire recomputes node it feeds.
+/// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+/// assert_eq!(evaluator.evaluate(&snarl, &mut Sum).unwrap(), [c]);
+/// assert_eq!(evaluator.output(OutPinId { node: c, output: 0 }), Some(&15));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Evaluator<V> {
+    /// Values of output pins of evaluated nodes.
+    outputs: HashMap<NodeId, Vec<V>>,
+
+    /// Wires connected to node inputs when the node was evaluated.
+    wires: HashMap<NodeId, Vec<Wire>>,
+
+    /// Nodes that must be recomputed.
+    dirty: HashSet<NodeId>,
+}
+
+impl<V> Default for Evaluator<V> {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl<V> Evaluator<V> {
+    /// Creates new evaluator with empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Evaluator {
+            outputs: HashMap::default(),
+            wires: HashMap::default(),
+            dirty: HashSet::default(),
+        }
+    }
+
+    /// Marks node as changed.
+    /// The node and all nodes downstream of it are recomputed on next evaluation.
+    ///
+    /// Changes of wires, added and removed nodes are detected automatically,
+    /// but changes of node values must be reported with this method.
+    pub fn invalidate(&mut self, node: NodeId) {
+        self.dirty.insert(node);
+    }
+
+    /// Drops all cached values.
+    /// All nodes are recomputed on next evaluation.
+    pub fn invalidate_all(&mut self) {
+        self.outputs.clear();
+        self.wires.clear();
+        self.dirty.clear();
+    }
+
+    /// Returns cached value of the output pin.
+    ///
+    /// Returns `None` if the node was not evaluated yet
+    /// or has not produced value for the pin.
+    #[must_use]
+    pub fn output(&self, pin: OutPinId) -> Option<&V> {
+        self.outputs.get(&pin.node)?.get(pin.output)
+    }
+
+    /// Returns cached values of all output pins of the node.
+    #[must_use]
+    pub fn node_outputs(&self, node: NodeId) -> Option<&[V]> {
+        self.outputs.get(&node).map(Vec::as_slice)
+    }
+
+    /// Iterates over cached values flowing into the input pin
+    /// through all its wires.
+    pub fn input<'a>(&'a self, pin: &'a InPin) -> impl Iterator<Item = &'a V> + 'a {
+        pin.remotes.iter().filter_map(|&remote| self.output(remote))
+    }
+
+    /// Evaluates nodes that changed since last evaluation
+    /// and all nodes downstream of them.
+    ///
+    /// Returns nodes that were recomputed in order of evaluation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Cycle`] if the graph contains a cycle.
+    /// Nothing is evaluated in this case.
+    pub fn evaluate<T, E>(&mut self, snarl: &Snarl<T>, eval: &mut E) -> Result<Vec<NodeId>, Cycle>
+    where
+        E: NodeEval<T, Value = V>,
+        V: Clone,
+    {
+        let order = snarl.topological_order()?;
+
+        // Forget removed nodes.
+        self.outputs.retain(|&node, _| snarl.contains_node(node));
+        self.wires.retain(|&node, _| snarl.contains_node(node));
+        self.dirty.retain(|&node| snarl.contains_node(node));
+
+        let mut recomputed = Vec::new();
+        let mut changed = HashSet::default();
+
+        for node in order {
+            let wires = snarl.wires.node_in_wires(node).collect::<Vec<_>>();
+
+            let dirty = self.dirty.remove(&node)
+                || !self.outputs.contains_key(&node)
+                || self.wires.get(&node) != Some(&wires)
+                || wires
+                    .iter()
+                    .any(|wire| changed.contains(&wire.out_pin.node));
+
+            if !dirty {
+                continue;
+            }
+
+            let value = &snarl[node];
+            let mut inputs = vec![Vec::new(); eval.inputs(value)];
+            for wire in &wires {
+                if let (Some(input), Some(v)) =
+                    (inputs.get_mut(wire.in_pin.input), self.output(wire.out_pin))
+                {
+                    input.push(v.clone());
+                }
+            }
+
+            let outputs = eval.evaluate(value, &inputs);
+            self.outputs.insert(node, outputs);
+            self.wires.insert(node, wires);
+
+            changed.insert(node);
+            recomputed.push(node);
+        }
+
+        Ok(recomputed)
+    }
+}
@@ -0,0 +1,268 @@
+//! Graph algorithms over nodes and wires of the [`Snarl`].
+//!
+//! Wires are treated as directed edges from the node with output pin
+//! to the node with input pin.
+
+use std::{collections::VecDeque, fmt};
+
+use egui::ahash::{HashMap, HashSet};
+
+use crate::{InPinId, NodeId, OutPinId, Snarl};
+
+/// Error returned when the graph contains a cycle
+/// and so nodes cannot be put in topological order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    /// Nodes that are part of any cycle, in ascending order.
+    pub nodes: Vec<NodeId>,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "graph contains cycle through nodes [")?;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{node}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl std::error::Error for Cycle {}
+
+impl<T> Snarl<T> {
+    /// Returns all nodes in dependency order.
+    /// Each node comes after all nodes that feed its inputs.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Cycle`] with nodes that are part of cycles if the graph is not acyclic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    /// let c = snarl.insert_node(egui::pos2(200.0, 0.0), ());
+    ///
+    /// snarl.connect(OutPinId { node: c, output: 0 }, InPinId { node: b, input: 0 });
+    /// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: a, input: 0 });
+    /// assert_eq!(snarl.topological_order().unwrap(), [c, b, a]);
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: c, input: 0 });
+    /// assert_eq!(snarl.topological_order().unwrap_err().nodes, [a, b, c]);
+    /// ```
+    pub fn topological_order(&self) -> Result<Vec<NodeId>, Cycle> {
+        let mut in_degree = HashMap::default();
+        let mut ready = VecDeque::new();
+
+        for (idx, node) in self.nodes.iter() {
+            let node = node.id(idx);
+            // Wires from removed nodes do not hold the node back.
+            let degree = self
+                .wires
+                .node_in_wires(node)
+                .filter(|wire| self.contains_node(wire.out_pin.node))
+                .count();
+            if degree == 0 {
+                ready.push_back(node);
+            }
+            in_degree.insert(node, degree);
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+
+            for wire in self.wires.node_out_wires(node) {
+                if let Some(degree) = in_degree.get_mut(&wire.in_pin.node) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.push_back(wire.in_pin.node);
+                    }
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            return Err(self.cycle());
+        }
+
+        Ok(order)
+    }
+
+    /// Checks if the graph contains a cycle.
+    #[must_use]
+    pub fn has_cycle(&self) -> bool {
+        self.topological_order().is_err()
+    }
+
+    /// Checks if connecting the pins would close a cycle.
+    ///
+    /// This is the case when the output pin's node is reachable from the input pin's node,
+    /// including connecting node to itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    ///
+    /// assert!(snarl.would_create_cycle(OutPinId { node: b, output: 0 }, InPinId { node: a, input: 0 }));
+    /// assert!(!snarl.would_create_cycle(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 1 }));
+    /// ```
+    #[must_use]
+    pub fn would_create_cycle(&self, from: OutPinId, to: InPinId) -> bool {
+        let mut visited = HashSet::default();
+        let mut stack = vec![to.node];
+
+        while let Some(node) = stack.pop() {
+            if node == from.node {
+                return true;
+            }
+            if visited.insert(node) {
+                stack.extend(self.successors(node));
+            }
+        }
+
+        false
+    }
+
+    /// Splits nodes into strongly connected components.
+    ///
+    /// Each node belongs to exactly one component.
+    /// Nodes of a component are reachable from each other,
+    /// so every component with more than one node contains a cycle.
+    /// Components are returned in reverse topological order -
+    /// component comes before all components that feed it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    /// let c = snarl.insert_node(egui::pos2(200.0, 0.0), ());
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    /// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: a, input: 0 });
+    /// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+    ///
+    /// let mut components = snarl.strongly_connected_components();
+    /// components.iter_mut().for_each(|component| component.sort());
+    /// assert_eq!(components, [vec![c], vec![a, b]]);
+    /// ```
+    #[must_use]
+    pub fn strongly_connected_components(&self) -> Vec<Vec<NodeId>> {
+        // Iterative Tarjan's algorithm.
+        let mut index = HashMap::default();
+        let mut lowlink = HashMap::default();
+        let mut on_stack = HashSet::default();
+        let mut stack = Vec::new();
+        let mut next_index = 0;
+        let mut components = Vec::new();
+
+        for (idx, node) in self.nodes.iter() {
+            let root = node.id(idx);
+            if index.contains_key(&root) {
+                continue;
+            }
+
+            index.insert(root, next_index);
+            lowlink.insert(root, next_index);
+            next_index += 1;
+            stack.push(root);
+            on_stack.insert(root);
+
+            // Emulated call stack with node, its successors and next successor to visit.
+            let mut calls = vec![(root, self.successors(root), 0)];
+
+            while let Some((node, successors, next)) = calls.last_mut() {
+                let node = *node;
+
+                if let Some(&succ) = successors.get(*next) {
+                    *next += 1;
+
+                    match index.get(&succ) {
+                        None => {
+                            index.insert(succ, next_index);
+                            lowlink.insert(succ, next_index);
+                            next_index += 1;
+                            stack.push(succ);
+                            on_stack.insert(succ);
+                            calls.push((succ, self.successors(succ), 0));
+                        }
+                        Some(&succ_index) => {
+                            if on_stack.contains(&succ) {
+                                let low = lowlink[&node];
+                                lowlink.insert(node, low.min(succ_index));
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                calls.pop();
+
+                let low = lowlink[&node];
+                if let Some(&(parent, _, _)) = calls.last() {
+                    let parent_low = lowlink[&parent];
+                    lowlink.insert(parent, parent_low.min(low));
+                }
+
+                if low == index[&node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(&member);
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+
+        components
+    }
+
+    /// Collects nodes that are part of any cycle.
+    fn cycle(&self) -> Cycle {
+        let mut nodes = self
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|component| match component[..] {
+                [node] => self.successors(node).contains(&node),
+                _ => true,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        nodes.sort();
+        Cycle { nodes }
+    }
+
+    /// Returns distinct nodes fed by the node directly.
+    fn successors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut successors = self
+            .wires
+            .node_out_wires(node)
+            .map(|wire| wire.in_pin.node)
+            .filter(|&succ| self.contains_node(succ))
+            .collect::<Vec<_>>();
+        successors.sort();
+        successors.dedup();
+        successors
+    }
+}
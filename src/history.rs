@@ -0,0 +1,489 @@
+//! Undo/redo history for [`Snarl`] edits.
+//!
+//! History is opt-in and enabled with [`Snarl::enable_history`].
+//! When enabled, every mutation made through [`Snarl`] methods
+//! records an edit that reverts it.
+//! Edits are grouped into steps that are undone and redone as a whole.
+
+use std::collections::VecDeque;
+
+use egui::Pos2;
+
+use crate::{Node, NodeId, NodeLayout, Snarl, Wire};
+
+/// Single reversible edit.
+///
+/// Applying an edit to the [`Snarl`] produces the edit that reverts it.
+#[derive(Clone, Debug)]
+pub(crate) enum Edit<T> {
+    /// Removes node that was inserted.
+    RemoveNode { node: NodeId },
+
+    /// Restores node that was removed together with its wires.
+    RestoreNode {
+        node: NodeId,
+        value: T,
+        pos: Pos2,
+        open: bool,
+        layout: Option<NodeLayout>,
+        wires: Vec<(Wire, Vec<Pos2>)>,
+    },
+
+    /// Inserts wire that was removed together with its reroute points.
+    Connect { wire: Wire, reroutes: Vec<Pos2> },
+
+    /// Removes wire that was inserted.
+    Disconnect { wire: Wire },
+
+    /// Moves node back to previous position.
+    Move { node: NodeId, pos: Pos2 },
+
+    /// Opens or collapses node back.
+    Open { node: NodeId, open: bool },
+
+    /// Restores previous node value.
+    Value { node: NodeId, value: T },
+
+    /// Restores previous reroute points of the wire.
+    Reroute { wire: Wire, points: Vec<Pos2> },
+}
+
+/// Property of the graph tracked by an edit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Pos(NodeId),
+    Open(NodeId),
+    Value(NodeId),
+    Reroutes(Wire),
+}
+
+impl<T> Edit<T> {
+    /// Returns property which is tracked by this edit.
+    /// Such edits need to be recorded only once per step.
+    fn property_of(&self) -> Option<Property> {
+        match *self {
+            Edit::Move { node, .. } => Some(Property::Pos(node)),
+            Edit::Open { node, .. } => Some(Property::Open(node)),
+            Edit::Value { node, .. } => Some(Property::Value(node)),
+            Edit::Reroute { wire, .. } => Some(Property::Reroutes(wire)),
+            _ => None,
+        }
+    }
+
+    /// Applies the edit and returns the edit that reverts it.
+    ///
+    /// Returns `None` if the edit no longer fits the Snarl,
+    /// for example if nodes were changed while history was disabled.
+    /// Such edit is skipped instead of touching unrelated nodes.
+    fn apply(self, snarl: &mut Snarl<T>) -> Option<Edit<T>> {
+        let edit = match self {
+            Edit::RemoveNode { node } => {
+                if !snarl.contains_node(node) {
+                    return None;
+                }
+
+                let wires = snarl
+                    .wires
+                    .wires_of_node(node)
+                    .map(|wire| (wire, snarl.wires.reroutes(&wire).to_vec()))
+                    .collect::<Vec<_>>();
+                snarl.wires.drop_node(node);
+
+                let Node {
+                    value,
+                    pos,
+                    open,
+                    layout,
+                    ..
+                } = snarl.nodes.remove(node.index);
+                snarl.draw_order.retain(|&idx| idx != node);
+                snarl.selected.retain(|&idx| idx != node);
+
+                Edit::RestoreNode {
+                    node,
+                    value,
+                    pos,
+                    open,
+                    layout,
+                    wires,
+                }
+            }
+            Edit::RestoreNode {
+                node,
+                value,
+                pos,
+                open,
+                layout,
+                wires,
+            } => {
+                let entry = Node {
+                    value,
+                    pos,
+                    open,
+                    generation: node.generation,
+                    layout,
+                };
+                if !snarl.restore_node(node, entry) {
+                    return None;
+                }
+                for (wire, reroutes) in wires {
+                    if snarl.contains_node(wire.out_pin.node)
+                        && snarl.contains_node(wire.in_pin.node)
+                    {
+                        snarl.wires.insert(wire);
+                        snarl.wires.set_reroutes(wire, reroutes);
+                    }
+                }
+                Edit::RemoveNode { node }
+            }
+            Edit::Connect { wire, reroutes } => {
+                if !snarl.contains_node(wire.out_pin.node) || !snarl.contains_node(wire.in_pin.node)
+                {
+                    return None;
+                }
+                snarl.wires.insert(wire);
+                snarl.wires.set_reroutes(wire, reroutes);
+                Edit::Disconnect { wire }
+            }
+            Edit::Disconnect { wire } if !snarl.wires.contains(&wire) => return None,
+            Edit::Disconnect { wire } => {
+                let reroutes = snarl.wires.set_reroutes(wire, Vec::new());
+                snarl.wires.remove(&wire);
+                Edit::Connect { wire, reroutes }
+            }
+            Edit::Move { node, .. } | Edit::Open { node, .. } | Edit::Value { node, .. }
+                if !snarl.contains_node(node) =>
+            {
+                return None;
+            }
+            Edit::Move { node, pos } => {
+                let old = std::mem::replace(&mut snarl.node_mut(node).pos, pos);
+                Edit::Move { node, pos: old }
+            }
+            Edit::Open { node, open } => {
+                let old = std::mem::replace(&mut snarl.node_mut(node).open, open);
+                Edit::Open { node, open: old }
+            }
+            Edit::Value { node, value } => {
+                let old = std::mem::replace(&mut snarl.node_mut(node).value, value);
+                Edit::Value { node, value: old }
+            }
+            Edit::Reroute { wire, .. } if !snarl.wires.contains(&wire) => return None,
+            Edit::Reroute { wire, points } => {
+                let old = snarl.wires.set_reroutes(wire, points);
+                Edit::Reroute { wire, points: old }
+            }
+        };
+        Some(edit)
+    }
+}
+
+/// Recorded history of [`Snarl`] edits.
+#[derive(Clone, Debug)]
+pub(crate) struct History<T> {
+    /// Steps that can be undone. Most recent step is at the back.
+    undo: VecDeque<Vec<Edit<T>>>,
+
+    /// Steps that can be redone. Most recent step is at the back.
+    redo: Vec<Vec<Edit<T>>>,
+
+    /// Edits of the step that is not finished yet.
+    pending: Vec<Edit<T>>,
+
+    /// Maximum number of steps that can be undone.
+    depth: usize,
+
+    /// Number of currently open groups.
+    groups: usize,
+
+    /// Keeps the step open after all groups are closed.
+    /// Used to merge edits of a gesture spanning multiple frames.
+    hold: bool,
+
+    /// Function to clone node values.
+    /// Stored here so that recording does not require `T: Clone` bound on every method.
+    pub(crate) clone: fn(&T) -> T,
+}
+
+impl<T> History<T> {
+    pub(crate) fn new(depth: usize, clone: fn(&T) -> T) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            pending: Vec::new(),
+            depth,
+            groups: 0,
+            hold: false,
+            clone,
+        }
+    }
+
+    /// Records edit that reverts a mutation.
+    pub(crate) fn record(&mut self, edit: Edit<T>) {
+        if let Some(property) = edit.property_of() {
+            // Only the earliest value of the property is needed to revert the step.
+            if self
+                .pending
+                .iter()
+                .any(|pending| pending.property_of() == Some(property))
+            {
+                return;
+            }
+        }
+
+        self.pending.push(edit);
+
+        if self.groups == 0 && !self.hold {
+            self.commit();
+        }
+    }
+
+    /// Returns true if value of the node is already recorded in pending step.
+    pub(crate) fn has_value(&self, node: NodeId) -> bool {
+        self.pending
+            .iter()
+            .any(|pending| pending.property_of() == Some(Property::Value(node)))
+    }
+
+    pub(crate) fn begin_group(&mut self) {
+        self.groups += 1;
+    }
+
+    pub(crate) fn end_group(&mut self) {
+        self.groups = self.groups.saturating_sub(1);
+        if self.groups == 0 && !self.hold {
+            self.commit();
+        }
+    }
+
+    pub(crate) fn set_hold(&mut self, hold: bool) {
+        self.hold = hold;
+        if !hold && self.groups == 0 {
+            self.commit();
+        }
+    }
+
+    /// Finishes pending step.
+    fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        self.undo.push_back(std::mem::take(&mut self.pending));
+        self.redo.clear();
+        self.trim();
+    }
+
+    pub(crate) fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        self.trim();
+    }
+
+    /// Drops oldest steps that exceed the depth.
+    fn trim(&mut self) {
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.pending.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending.clear();
+    }
+}
+
+impl<T> Snarl<T> {
+    /// Enables recording of edits for undo and redo.
+    ///
+    /// Up to `depth` steps are kept.
+    /// If history is already enabled, only its depth is changed.
+    ///
+    /// Node values edited through [`Snarl::edit_node`] are recorded by cloning
+    /// the value before the edit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::Snarl;
+    /// let mut snarl = Snarl::<i32>::new();
+    /// snarl.enable_history(100);
+    ///
+    /// let node = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+    /// *snarl.edit_node(node).unwrap() = 2;
+    ///
+    /// assert!(snarl.undo());
+    /// assert_eq!(snarl[node], 1);
+    ///
+    /// assert!(snarl.undo());
+    /// assert!(snarl.get_node(node).is_none());
+    ///
+    /// assert!(snarl.redo());
+    /// assert!(snarl.redo());
+    /// assert_eq!(snarl[node], 2);
+    /// ```
+    pub fn enable_history(&mut self, depth: usize)
+    where
+        T: Clone,
+    {
+        match &mut self.history {
+            Some(history) => history.set_depth(depth),
+            None => self.history = Some(Box::new(History::new(depth, T::clone))),
+        }
+    }
+
+    /// Disables history and drops all recorded steps.
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// Returns true if history is enabled.
+    #[must_use]
+    pub fn history_enabled(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Drops all recorded steps, keeping history enabled.
+    pub fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    /// Returns true if there is a step to undo.
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().is_some_and(|h| h.can_undo())
+    }
+
+    /// Returns true if there is a step to redo.
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().is_some_and(|h| h.can_redo())
+    }
+
+    /// Reverts last recorded step.
+    /// Unfinished step is finished first.
+    ///
+    /// Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(mut history) = self.history.take() else {
+            return false;
+        };
+
+        history.groups = 0;
+        history.hold = false;
+        history.commit();
+
+        let undone = match history.undo.pop_back() {
+            None => false,
+            Some(step) => {
+                let redo = self.apply_step(step);
+                history.redo.push(redo);
+                true
+            }
+        };
+
+        self.history = Some(history);
+        undone
+    }
+
+    /// Re-applies last undone step.
+    ///
+    /// Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(mut history) = self.history.take() else {
+            return false;
+        };
+
+        let redone = match history.redo.pop() {
+            None => false,
+            Some(step) => {
+                let undo = self.apply_step(step);
+                history.undo.push_back(undo);
+                history.trim();
+                true
+            }
+        };
+
+        self.history = Some(history);
+        redone
+    }
+
+    /// Starts a group of edits that will be undone as a single step.
+    ///
+    /// Groups can be nested. The step is finished when the outermost group ends.
+    pub fn begin_history_group(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.begin_group();
+        }
+    }
+
+    /// Ends a group of edits started with [`Snarl::begin_history_group`].
+    pub fn end_history_group(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.end_group();
+        }
+    }
+
+    /// Returns mutable reference to the node, recording its current value
+    /// so that the edit can be undone.
+    ///
+    /// Value is recorded once per step, so repeated edits within a step are merged.
+    /// Without history this is the same as [`Snarl::get_node_mut`].
+    pub fn edit_node(&mut self, node: NodeId) -> Option<&mut T> {
+        let entry = self
+            .nodes
+            .get_mut(node.index)
+            .filter(|entry| entry.generation == node.generation)?;
+        if let Some(history) = &mut self.history {
+            if !history.has_value(node) {
+                history.record(Edit::Value {
+                    node,
+                    value: (history.clone)(&entry.value),
+                });
+            }
+        }
+        Some(&mut entry.value)
+    }
+
+    /// Applies edits of the step in reverse order.
+    /// Returns step that reverts it.
+    fn apply_step(&mut self, step: Vec<Edit<T>>) -> Vec<Edit<T>> {
+        // Reverting edits are collected in reverse order,
+        // so applying them in reverse again replays the step forward.
+        step.into_iter()
+            .rev()
+            .filter_map(|edit| edit.apply(self))
+            .collect()
+    }
+
+    /// Puts node back under the same identifier.
+    ///
+    /// Returns false if the slot is taken by another node,
+    /// which is kept intact.
+    fn restore_node(&mut self, id: NodeId, node: Node<T>) -> bool {
+        if self.nodes.contains(id.index) {
+            return false;
+        }
+        if self.nodes.vacant_key() == id.index {
+            self.nodes.insert(node);
+        } else {
+            let nodes = std::mem::take(&mut self.nodes);
+            self.nodes = nodes
+                .into_iter()
+                .chain(std::iter::once((id.index, node)))
+                .collect();
+        }
+        self.draw_order.push(id);
+        true
+    }
+}
@@ -0,0 +1,108 @@
+//! Automatic placement of nodes of the [`Snarl`].
+//!
+//! Layouts only change positions of nodes.
+//! Wires are treated as directed edges from the node with output pin
+//! to the node with input pin.
+//!
+//! Sizes of nodes are taken from [`Snarl::node_rect`],
+//! so layouts give best results after the Snarl was shown at least once.
+//! Positions are changed as a single history step that can be undone,
+//! except for incremental steps of [`ForceLayout`].
+
+use egui::{Pos2, Vec2};
+
+use crate::{NodeId, Snarl};
+
+mod force;
+mod layered;
+
+pub use self::{force::ForceLayout, layered::LayeredLayout};
+
+/// Direction in which wires flow through the layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayoutDirection {
+    /// Wires go from left to right.
+    #[default]
+    LeftToRight,
+
+    /// Wires go from right to left.
+    RightToLeft,
+
+    /// Wires go from top to bottom.
+    TopToBottom,
+
+    /// Wires go from bottom to top.
+    BottomToTop,
+}
+
+impl LayoutDirection {
+    /// Splits size into extents along and across the direction.
+    fn split(self, size: Vec2) -> (f32, f32) {
+        match self {
+            LayoutDirection::LeftToRight | LayoutDirection::RightToLeft => (size.x, size.y),
+            LayoutDirection::TopToBottom | LayoutDirection::BottomToTop => (size.y, size.x),
+        }
+    }
+
+    /// Returns position of the node rect with given size
+    /// which starts at `main` along the direction and at `cross` across it.
+    fn pos(self, main: f32, cross: f32, size: Vec2) -> Pos2 {
+        match self {
+            LayoutDirection::LeftToRight => Pos2::new(main, cross),
+            LayoutDirection::RightToLeft => Pos2::new(-main - size.x, cross),
+            LayoutDirection::TopToBottom => Pos2::new(cross, main),
+            LayoutDirection::BottomToTop => Pos2::new(cross, -main - size.y),
+        }
+    }
+}
+
+impl<T> Snarl<T> {
+    /// Returns existing nodes to lay out without duplicates.
+    /// All nodes are returned if `nodes` is `None`.
+    fn layout_nodes(&self, nodes: Option<Vec<NodeId>>) -> Vec<NodeId> {
+        match nodes {
+            None => self.nodes.iter().map(|(idx, node)| node.id(idx)).collect(),
+            Some(mut nodes) => {
+                let mut seen = egui::ahash::HashSet::default();
+                nodes.retain(|&node| self.contains_node(node) && seen.insert(node));
+                nodes
+            }
+        }
+    }
+
+    /// Returns measured size of the node or `default` if it was never shown.
+    fn layout_size(&self, node: NodeId, default: Vec2) -> Vec2 {
+        self.node_rect(node).map_or(default, |rect| rect.size())
+    }
+
+    /// Moves nodes to new positions.
+    ///
+    /// Positions are shifted together so that the top-left corner
+    /// of the laid out nodes stays in place.
+    fn place_nodes(&mut self, positions: Vec<(NodeId, Pos2)>) {
+        let old_min = positions
+            .iter()
+            .map(|&(node, _)| self.node(node).pos)
+            .fold(Pos2::new(f32::INFINITY, f32::INFINITY), Pos2::min);
+        let new_min = positions
+            .iter()
+            .map(|&(_, pos)| pos)
+            .fold(Pos2::new(f32::INFINITY, f32::INFINITY), Pos2::min);
+        let shift = old_min - new_min;
+
+        self.move_nodes(positions.into_iter().map(|(node, pos)| (node, pos + shift)));
+    }
+
+    /// Moves nodes to new positions as a single history step.
+    fn move_nodes(&mut self, positions: impl IntoIterator<Item = (NodeId, Pos2)>) {
+        self.begin_history_group();
+        for (node, pos) in positions {
+            let delta = pos - self.node(node).pos;
+            if delta != Vec2::ZERO {
+                self.move_node(node, delta);
+            }
+        }
+        self.end_history_group();
+    }
+}
@@ -0,0 +1,334 @@
+use egui::{
+    ahash::{HashMap, HashSet},
+    Pos2, Vec2,
+};
+
+use crate::{NodeId, Snarl};
+
+/// Temperature below which the layout is considered settled.
+const SETTLED_TEMPERATURE: f32 = 0.5;
+
+/// Force-directed layout.
+///
+/// Nodes repel each other while wires pull connected nodes together,
+/// regardless of wire direction, so it suits graphs with cycles or without clear flow.
+///
+/// Layout is incremental. Each iteration moves nodes by at most current temperature,
+/// which cools down until the layout settles.
+/// Call [`ForceLayout::step`] every frame to animate nodes into place
+/// or [`ForceLayout::run`] to lay out the graph at once.
+///
+/// Pinned nodes are never moved, but still push and pull other nodes.
+/// Pin nodes that the user has placed manually, for example [`SnarlResponse::moved_nodes`](crate::ui::SnarlResponse::moved_nodes).
+///
+/// # Examples
+///
+/// ```
+/// # use egui_snarl::{InPinId, OutPinId, Snarl, layout::ForceLayout};
+/// let mut snarl = Snarl::new();
+/// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+/// let b = snarl.insert_node(egui::pos2(1000.0, 0.0), ());
+/// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+///
+/// let mut layout = ForceLayout::new();
+/// layout.pin(a);
+/// while layout.step(&mut snarl, 10) {}
+///
+/// let pos = |node| snarl.nodes_pos_ids().find(|&(id, _, _)| id == node).unwrap().1;
+/// assert_eq!(pos(a), egui::pos2(0.0, 0.0));
+/// assert!(pos(b).x < 1000.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ForceLayout {
+    /// Preferred gap between connected nodes.
+    pub spring_length: f32,
+
+    /// How strongly wires pull connected nodes to the preferred gap.
+    pub spring_stiffness: f32,
+
+    /// How strongly nodes push each other apart.
+    pub repulsion: f32,
+
+    /// How strongly nodes are pulled towards their common center.
+    /// Keeps disconnected parts of the graph together.
+    pub gravity: f32,
+
+    /// Maximum distance a node moves in one iteration
+    /// when the layout starts or restarts.
+    pub max_step: f32,
+
+    /// Factor by which temperature is multiplied after each iteration.
+    pub cooling: f32,
+
+    /// Size assumed for nodes that were never shown.
+    pub default_node_size: Vec2,
+
+    /// Current temperature relative to `max_step`.
+    heat: f32,
+    pinned: HashSet<NodeId>,
+}
+
+impl ForceLayout {
+    /// Creates new [`ForceLayout`] filled with default values.
+    #[must_use]
+    pub fn new() -> Self {
+        ForceLayout {
+            spring_length: 80.0,
+            spring_stiffness: 0.05,
+            repulsion: 0.05,
+            gravity: 0.01,
+            max_step: 40.0,
+            cooling: 0.97,
+            default_node_size: Vec2::new(150.0, 100.0),
+            heat: 1.0,
+            pinned: HashSet::default(),
+        }
+    }
+
+    /// Pins the node, so layout does not move it.
+    pub fn pin(&mut self, node: NodeId) {
+        self.pinned.insert(node);
+    }
+
+    /// Unpins the node, so layout can move it again.
+    pub fn unpin(&mut self, node: NodeId) {
+        self.pinned.remove(&node);
+    }
+
+    /// Returns true if the node is pinned.
+    #[must_use]
+    pub fn is_pinned(&self, node: NodeId) -> bool {
+        self.pinned.contains(&node)
+    }
+
+    /// Returns pinned nodes in arbitrary order.
+    pub fn pinned_nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.pinned.iter().copied()
+    }
+
+    /// Unpins all nodes.
+    pub fn clear_pins(&mut self) {
+        self.pinned.clear();
+    }
+
+    /// Heats the layout up, so nodes start moving again.
+    ///
+    /// Call this after the graph was changed, for example after nodes or wires were added.
+    pub fn restart(&mut self) {
+        self.heat = 1.0;
+    }
+
+    /// Returns true if nodes stopped moving.
+    #[must_use]
+    pub fn is_settled(&self) -> bool {
+        self.temperature() < SETTLED_TEMPERATURE
+    }
+
+    /// Returns maximum distance a node moves in the next iteration.
+    fn temperature(&self) -> f32 {
+        self.heat * self.max_step
+    }
+
+    /// Runs up to `iterations` iterations of the layout.
+    /// Returns true if the layout is not settled yet,
+    /// so the caller should request repaint and call it again next frame.
+    ///
+    /// Steps are not recorded in history, since they happen every frame.
+    pub fn step<T>(&mut self, snarl: &mut Snarl<T>, iterations: usize) -> bool {
+        let Some(mut sim) = self.simulation(snarl) else {
+            return false;
+        };
+
+        for _ in 0..iterations {
+            if self.is_settled() {
+                break;
+            }
+            self.iterate(&mut sim);
+        }
+
+        for (i, &node) in sim.nodes.iter().enumerate() {
+            if !sim.fixed[i] {
+                snarl.node_mut(node).pos = sim.centers[i] - sim.sizes[i] / 2.0;
+            }
+        }
+
+        !self.is_settled()
+    }
+
+    /// Runs the layout until it settles or `max_iterations` iterations are done.
+    ///
+    /// New positions are recorded as a single history step.
+    pub fn run<T>(&mut self, snarl: &mut Snarl<T>, max_iterations: usize) {
+        let Some(mut sim) = self.simulation(snarl) else {
+            return;
+        };
+
+        for _ in 0..max_iterations {
+            if self.is_settled() {
+                break;
+            }
+            self.iterate(&mut sim);
+        }
+
+        snarl.move_nodes(
+            sim.nodes
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| !sim.fixed[i])
+                .map(|(i, &node)| (node, sim.centers[i] - sim.sizes[i] / 2.0)),
+        );
+    }
+
+    /// Collects nodes and wires of the Snarl.
+    /// Returns `None` if there is nothing to move.
+    fn simulation<T>(&mut self, snarl: &Snarl<T>) -> Option<Simulation> {
+        self.pinned.retain(|&node| snarl.contains_node(node));
+
+        if self.is_settled() {
+            return None;
+        }
+
+        let nodes = snarl.layout_nodes(None);
+        if nodes.iter().all(|node| self.pinned.contains(node)) {
+            self.heat = 0.0;
+            return None;
+        }
+
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, &node)| (node, idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut edges = Vec::new();
+        for (from, &node) in nodes.iter().enumerate() {
+            for wire in snarl.wires.node_out_wires(node) {
+                if let Some(&to) = index.get(&wire.in_pin.node) {
+                    if from != to {
+                        edges.push((from.min(to), from.max(to)));
+                    }
+                }
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+
+        let sizes = nodes
+            .iter()
+            .map(|&node| snarl.layout_size(node, self.default_node_size))
+            .collect::<Vec<_>>();
+        let centers = nodes
+            .iter()
+            .zip(&sizes)
+            .map(|(&node, &size)| snarl.node(node).pos + size / 2.0)
+            .collect();
+        let fixed = nodes
+            .iter()
+            .map(|node| self.pinned.contains(node))
+            .collect();
+
+        Some(Simulation {
+            nodes,
+            centers,
+            sizes,
+            fixed,
+            edges,
+        })
+    }
+
+    /// Moves nodes once and cools the layout down.
+    fn iterate(&mut self, sim: &mut Simulation) {
+        let len = sim.nodes.len();
+        let mut forces = vec![Vec2::ZERO; len];
+
+        for i in 0..len {
+            for j in i + 1..len {
+                let (dir, gap) = sim.gap(i, j);
+                // Overlapping nodes are pushed apart with large but bounded force.
+                let gap = gap.max(self.spring_length * 0.1);
+                let force = self.repulsion * self.spring_length.powi(3) / (gap * gap);
+                forces[i] -= dir * force;
+                forces[j] += dir * force;
+            }
+        }
+
+        for &(i, j) in &sim.edges {
+            let (dir, gap) = sim.gap(i, j);
+            let force = self.spring_stiffness * (gap - self.spring_length);
+            forces[i] += dir * force;
+            forces[j] -= dir * force;
+        }
+
+        let center = sim
+            .centers
+            .iter()
+            .fold(Vec2::ZERO, |sum, c| sum + c.to_vec2())
+            / len as f32;
+        for (force, c) in forces.iter_mut().zip(&sim.centers) {
+            *force += (center - c.to_vec2()) * self.gravity;
+        }
+
+        let temperature = self.temperature();
+        let mut max_move = 0.0f32;
+        for ((force, c), &fixed) in forces.iter().zip(&mut sim.centers).zip(&sim.fixed) {
+            if fixed {
+                continue;
+            }
+            let length = force.length();
+            if length > 0.0 {
+                let step = *force * (length.min(temperature) / length);
+                *c += step;
+                max_move = max_move.max(step.length());
+            }
+        }
+
+        self.heat *= self.cooling;
+
+        // Forces are balanced, no need to wait for cooling.
+        if max_move < SETTLED_TEMPERATURE * 0.1 {
+            self.heat = 0.0;
+        }
+    }
+}
+
+impl Default for ForceLayout {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Nodes being laid out.
+struct Simulation {
+    nodes: Vec<NodeId>,
+
+    /// Centers of the nodes.
+    centers: Vec<Pos2>,
+    sizes: Vec<Vec2>,
+
+    /// Whether node is pinned.
+    fixed: Vec<bool>,
+
+    /// Pairs of connected nodes.
+    edges: Vec<(usize, usize)>,
+}
+
+impl Simulation {
+    /// Returns direction from node `i` to node `j`
+    /// and distance between their borders along it.
+    fn gap(&self, i: usize, j: usize) -> (Vec2, f32) {
+        let delta = self.centers[j] - self.centers[i];
+        let distance = delta.length();
+        let dir = if distance > f32::EPSILON {
+            delta / distance
+        } else {
+            // Nodes at the same place are pushed apart in arbitrary but stable direction.
+            Vec2::angled((i * 7 + j) as f32)
+        };
+
+        // Distance from the center to the border of the node in given direction.
+        let radius = |size: Vec2| (size.x * dir.x.abs() + size.y * dir.y.abs()) / 2.0;
+        let gap = distance - radius(self.sizes[i]) - radius(self.sizes[j]);
+        (dir, gap)
+    }
+}
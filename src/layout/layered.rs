@@ -0,0 +1,481 @@
+use egui::{ahash::HashMap, Pos2, Vec2};
+
+use crate::{NodeId, Snarl};
+
+use super::LayoutDirection;
+
+/// Number of passes that align nodes with their neighbors.
+const ALIGN_PASSES: usize = 8;
+
+/// Layered layout of directed graphs, also known as Sugiyama layout.
+///
+/// Nodes are assigned to layers so that wires go from earlier layers to later ones.
+/// Wires that close cycles are treated as reversed.
+/// Nodes in each layer are then ordered to reduce wire crossings
+/// and positioned close to the nodes they are connected to.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_snarl::{InPinId, OutPinId, Snarl, layout::{LayeredLayout, LayoutDirection}};
+/// let mut snarl = Snarl::new();
+/// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+/// let b = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+/// let c = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+/// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+/// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+///
+/// let layout = LayeredLayout {
+///     direction: LayoutDirection::TopToBottom,
+///     ..LayeredLayout::new()
+/// };
+/// layout.apply(&mut snarl);
+///
+/// let pos = |node| snarl.nodes_pos_ids().find(|&(id, _, _)| id == node).unwrap().1;
+/// assert!(pos(a).y < pos(b).y && pos(b).y < pos(c).y);
+/// assert_eq!(pos(a).x, pos(c).x);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayeredLayout {
+    /// Direction in which wires flow.
+    pub direction: LayoutDirection,
+
+    /// Gap between adjacent layers.
+    pub layer_spacing: f32,
+
+    /// Gap between adjacent nodes in a layer.
+    pub node_spacing: f32,
+
+    /// Number of sweeps that reorder nodes to reduce wire crossings.
+    pub sweeps: usize,
+
+    /// Size assumed for nodes that were never shown.
+    pub default_node_size: Vec2,
+}
+
+impl LayeredLayout {
+    /// Creates new [`LayeredLayout`] filled with default values.
+    #[must_use]
+    pub const fn new() -> Self {
+        LayeredLayout {
+            direction: LayoutDirection::LeftToRight,
+            layer_spacing: 80.0,
+            node_spacing: 30.0,
+            sweeps: 8,
+            default_node_size: Vec2::new(150.0, 100.0),
+        }
+    }
+
+    /// Lays out all nodes of the Snarl.
+    pub fn apply<T>(&self, snarl: &mut Snarl<T>) {
+        let nodes = snarl.layout_nodes(None);
+        self.layout(snarl, &nodes);
+    }
+
+    /// Lays out given nodes, leaving other nodes in place.
+    ///
+    /// Only wires between given nodes are considered.
+    /// Nodes that do not exist are ignored.
+    pub fn apply_to<T>(&self, snarl: &mut Snarl<T>, nodes: impl IntoIterator<Item = NodeId>) {
+        let nodes = snarl.layout_nodes(Some(nodes.into_iter().collect()));
+        self.layout(snarl, &nodes);
+    }
+
+    fn layout<T>(&self, snarl: &mut Snarl<T>, nodes: &[NodeId]) {
+        if nodes.is_empty() {
+            return;
+        }
+        let positions = self.positions(snarl, nodes);
+        snarl.place_nodes(positions);
+    }
+
+    /// Computes new positions of the nodes.
+    fn positions<T>(&self, snarl: &Snarl<T>, nodes: &[NodeId]) -> Vec<(NodeId, Pos2)> {
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, &node)| (node, idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut edges = Vec::new();
+        for (from, &node) in nodes.iter().enumerate() {
+            for wire in snarl.wires.node_out_wires(node) {
+                if let Some(&to) = index.get(&wire.in_pin.node) {
+                    if from != to {
+                        edges.push((from, to));
+                    }
+                }
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+
+        break_cycles(nodes.len(), &mut edges);
+        let ranks = assign_layers(nodes.len(), &edges);
+
+        let mut graph = LayerGraph::new(&ranks, &edges);
+
+        // Start with nodes ordered as they are placed now.
+        let mut cross_of = nodes
+            .iter()
+            .map(|&node| {
+                let center = snarl.node(node).pos + self.layout_size(snarl, node) / 2.0;
+                self.direction.split(center.to_vec2()).1
+            })
+            .collect::<Vec<_>>();
+        for &(from, to) in &graph.dummies {
+            cross_of.push((cross_of[from] + cross_of[to]) / 2.0);
+        }
+        for layer in &mut graph.layers {
+            layer.sort_by(|&a, &b| cross_of[a].total_cmp(&cross_of[b]));
+        }
+
+        graph.reduce_crossings(self.sweeps);
+
+        // Dummy nodes have no size, so long wires pass between nodes.
+        let extents = (0..graph.len())
+            .map(|v| match nodes.get(v) {
+                Some(&node) => self.direction.split(self.layout_size(snarl, node)),
+                None => (0.0, 0.0),
+            })
+            .collect::<Vec<_>>();
+
+        let mut main = Vec::with_capacity(graph.layers.len());
+        let mut next = 0.0;
+        for layer in &graph.layers {
+            main.push(next);
+            let thickness = layer.iter().map(|&v| extents[v].0).fold(0.0, f32::max);
+            next += thickness + self.layer_spacing;
+        }
+
+        let cross = graph.align(&extents, self.node_spacing);
+
+        nodes
+            .iter()
+            .enumerate()
+            .map(|(v, &node)| {
+                let size = self.layout_size(snarl, node);
+                let cross = cross[v] - extents[v].1 / 2.0;
+                (node, self.direction.pos(main[ranks[v]], cross, size))
+            })
+            .collect()
+    }
+
+    fn layout_size<T>(&self, snarl: &Snarl<T>, node: NodeId) -> Vec2 {
+        snarl.layout_size(node, self.default_node_size)
+    }
+}
+
+impl Default for LayeredLayout {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reverses edges that close cycles, so that the graph becomes acyclic.
+fn break_cycles(len: usize, edges: &mut Vec<(usize, usize)>) {
+    let mut out = vec![Vec::new(); len];
+    for (idx, &(from, _)) in edges.iter().enumerate() {
+        out[from].push(idx);
+    }
+
+    // 0 - not visited, 1 - on the stack, 2 - finished.
+    let mut state = vec![0u8; len];
+    let mut reversed = Vec::new();
+
+    for root in 0..len {
+        if state[root] != 0 {
+            continue;
+        }
+        state[root] = 1;
+        let mut stack = vec![(root, 0)];
+
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            if let Some(&edge) = out[v].get(*next) {
+                *next += 1;
+                let to = edges[edge].1;
+                match state[to] {
+                    0 => {
+                        state[to] = 1;
+                        stack.push((to, 0));
+                    }
+                    1 => reversed.push(edge),
+                    _ => {}
+                }
+            } else {
+                state[v] = 2;
+                stack.pop();
+            }
+        }
+    }
+
+    for edge in reversed {
+        let (from, to) = edges[edge];
+        edges[edge] = (to, from);
+    }
+    edges.sort_unstable();
+    edges.dedup();
+}
+
+/// Assigns layers to nodes of acyclic graph.
+///
+/// Each node is placed right after the latest of its predecessors.
+/// Nodes without predecessors are then moved right before the earliest of their successors.
+fn assign_layers(len: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut succs = vec![Vec::new(); len];
+    let mut in_degree = vec![0; len];
+    for &(from, to) in edges {
+        succs[from].push(to);
+        in_degree[to] += 1;
+    }
+
+    let mut order = (0..len).filter(|&v| in_degree[v] == 0).collect::<Vec<_>>();
+    let mut next = 0;
+    while let Some(&v) = order.get(next) {
+        next += 1;
+        for &to in &succs[v] {
+            in_degree[to] -= 1;
+            if in_degree[to] == 0 {
+                order.push(to);
+            }
+        }
+    }
+
+    let mut ranks = vec![0; len];
+    let mut has_preds = vec![false; len];
+    for &v in &order {
+        for &to in &succs[v] {
+            ranks[to] = ranks[to].max(ranks[v] + 1);
+            has_preds[to] = true;
+        }
+    }
+
+    for &v in order.iter().rev() {
+        if !has_preds[v] {
+            if let Some(rank) = succs[v].iter().map(|&to| ranks[to]).min() {
+                ranks[v] = rank - 1;
+            }
+        }
+    }
+
+    ranks
+}
+
+/// Graph with nodes split into layers where edges connect only adjacent layers.
+///
+/// First vertices are the laid out nodes, followed by dummy vertices
+/// that split edges spanning several layers.
+struct LayerGraph {
+    /// Vertices of each layer in order.
+    layers: Vec<Vec<usize>>,
+
+    /// Neighbors of each vertex in the previous layer.
+    up: Vec<Vec<usize>>,
+
+    /// Neighbors of each vertex in the next layer.
+    down: Vec<Vec<usize>>,
+
+    /// Ends of the original edge for each dummy vertex.
+    dummies: Vec<(usize, usize)>,
+}
+
+impl LayerGraph {
+    fn new(ranks: &[usize], edges: &[(usize, usize)]) -> Self {
+        let len = ranks.len();
+        let mut layers = vec![Vec::new(); ranks.iter().max().map_or(0, |&rank| rank + 1)];
+        for (v, &rank) in ranks.iter().enumerate() {
+            layers[rank].push(v);
+        }
+
+        let mut graph = LayerGraph {
+            layers,
+            up: vec![Vec::new(); len],
+            down: vec![Vec::new(); len],
+            dummies: Vec::new(),
+        };
+
+        for &(from, to) in edges {
+            let mut prev = from;
+            for rank in ranks[from] + 1..ranks[to] {
+                let dummy = len + graph.dummies.len();
+                graph.dummies.push((from, to));
+                graph.up.push(Vec::new());
+                graph.down.push(Vec::new());
+                graph.layers[rank].push(dummy);
+                graph.link(prev, dummy);
+                prev = dummy;
+            }
+            graph.link(prev, to);
+        }
+
+        graph
+    }
+
+    fn len(&self) -> usize {
+        self.up.len()
+    }
+
+    fn link(&mut self, from: usize, to: usize) {
+        self.down[from].push(to);
+        self.up[to].push(from);
+    }
+
+    /// Reorders vertices in layers with barycenter heuristic,
+    /// keeping the order with the least number of crossings.
+    fn reduce_crossings(&mut self, sweeps: usize) {
+        let mut index = vec![0.0; self.len()];
+        let mut best = self.layers.clone();
+        let mut best_crossings = self.crossings();
+
+        for sweep in 0..sweeps {
+            let downward = sweep % 2 == 0;
+            let mut ranks = (0..self.layers.len()).collect::<Vec<_>>();
+            if !downward {
+                ranks.reverse();
+            }
+
+            for rank in ranks {
+                for (i, &v) in self.layers[rank].iter().enumerate() {
+                    index[v] = i as f32;
+                }
+
+                let neighbors = if downward { &self.up } else { &self.down };
+                let mut keys = self.layers[rank]
+                    .iter()
+                    .map(|&v| (v, barycenter(&neighbors[v], &index).unwrap_or(index[v])))
+                    .collect::<Vec<_>>();
+                keys.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+                for (i, &(v, _)) in keys.iter().enumerate() {
+                    self.layers[rank][i] = v;
+                    index[v] = i as f32;
+                }
+            }
+
+            let crossings = self.crossings();
+            if crossings < best_crossings {
+                best_crossings = crossings;
+                best.clone_from(&self.layers);
+            }
+        }
+
+        self.layers = best;
+    }
+
+    /// Counts crossings of edges between all adjacent layers.
+    fn crossings(&self) -> usize {
+        let mut index = vec![0; self.len()];
+        for layer in &self.layers {
+            for (i, &v) in layer.iter().enumerate() {
+                index[v] = i;
+            }
+        }
+
+        let mut crossings = 0;
+        for layer in &self.layers {
+            let edges = layer
+                .iter()
+                .flat_map(|&v| self.down[v].iter().map(move |&to| (v, to)))
+                .map(|(from, to)| (index[from], index[to]))
+                .collect::<Vec<_>>();
+
+            for (i, &(a, b)) in edges.iter().enumerate() {
+                crossings += edges[i + 1..]
+                    .iter()
+                    .filter(|&&(c, d)| (a < c && b > d) || (a > c && b < d))
+                    .count();
+            }
+        }
+        crossings
+    }
+
+    /// Returns centers of vertices across layers.
+    ///
+    /// Vertices are moved towards their neighbors in alternating passes,
+    /// keeping order and gaps between vertices in each layer.
+    fn align(&self, extents: &[(f32, f32)], spacing: f32) -> Vec<f32> {
+        let mut center = vec![0.0; self.len()];
+
+        for layer in &self.layers {
+            let desired = vec![0.0; layer.len()];
+            self.spread(layer, &desired, extents, spacing, &mut center);
+        }
+
+        for pass in 0..ALIGN_PASSES {
+            let downward = pass % 2 == 0;
+            let neighbors = if downward { &self.up } else { &self.down };
+            let mut ranks = (0..self.layers.len()).collect::<Vec<_>>();
+            if !downward {
+                ranks.reverse();
+            }
+
+            for rank in ranks {
+                let layer = &self.layers[rank];
+                let desired = layer
+                    .iter()
+                    .map(|&v| barycenter(&neighbors[v], &center).unwrap_or(center[v]))
+                    .collect::<Vec<_>>();
+                self.spread(layer, &desired, extents, spacing, &mut center);
+            }
+        }
+
+        center
+    }
+
+    /// Places vertices of the layer as close to desired centers as possible
+    /// without overlapping.
+    fn spread(
+        &self,
+        layer: &[usize],
+        desired: &[f32],
+        extents: &[(f32, f32)],
+        spacing: f32,
+        center: &mut [f32],
+    ) {
+        // Offset of each vertex from the first one when packed tightly.
+        let mut offsets = Vec::with_capacity(layer.len());
+        let mut offset = 0.0;
+        for (i, &v) in layer.iter().enumerate() {
+            if i > 0 {
+                let prev = layer[i - 1];
+                offset += (extents[prev].1 + extents[v].1) / 2.0 + spacing;
+            }
+            offsets.push(offset);
+        }
+
+        // Offsets turn gap constraints into ordering of shifts.
+        // Best non-decreasing shifts are found by pooling adjacent violators.
+        let mut blocks: Vec<(f32, usize)> = Vec::new();
+        for (d, o) in desired.iter().zip(&offsets) {
+            blocks.push((d - o, 1));
+            while let [.., (sum_a, len_a), (sum_b, len_b)] = blocks[..] {
+                if sum_a / len_a as f32 <= sum_b / len_b as f32 {
+                    break;
+                }
+                blocks.pop();
+                *blocks.last_mut().unwrap() = (sum_a + sum_b, len_a + len_b);
+            }
+        }
+
+        let mut i = 0;
+        for (sum, len) in blocks {
+            let shift = sum / len as f32;
+            for _ in 0..len {
+                center[layer[i]] = shift + offsets[i];
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Returns mean value of the vertices.
+fn barycenter(vertices: &[usize], value: &[f32]) -> Option<f32> {
+    if vertices.is_empty() {
+        return None;
+    }
+    let sum: f32 = vertices.iter().map(|&v| value[v]).sum();
+    Some(sum / vertices.len() as f32)
+}
@@ -10,13 +10,30 @@
 // #![warn(clippy::pedantic)]
 #![allow(clippy::inline_always)]
 
+mod clipboard;
+mod error;
+pub mod eval;
+mod graph;
+mod history;
+pub mod layout;
 pub mod ui;
 
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::{Index, IndexMut, RangeInclusive},
+};
 
-use egui::{ahash::HashSet, Pos2};
+use egui::{
+    ahash::{HashMap, HashSet},
+    Pos2,
+};
 use slab::Slab;
 
+pub use self::{error::SnarlError, graph::Cycle};
+
+use self::history::{Edit, History};
+
 impl<T> Default for Snarl<T> {
     fn default() -> Self {
         Snarl::new()
@@ -25,12 +42,94 @@ impl<T> Default for Snarl<T> {
 
 /// Node identifier.
 ///
-/// This is newtype wrapper around [`usize`] that implements
-/// necessary traits, but omits arithmetic operations.
+/// Consists of index of the node's slot in the Snarl and generation of the node.
+/// Every inserted node gets new generation, so identifier of removed node
+/// never refers to another node that reuses the same slot.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_snarl::Snarl;
+/// let mut snarl = Snarl::new();
+/// let a = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+/// snarl.remove_node(a);
+///
+/// let b = snarl.insert_node(egui::pos2(0.0, 0.0), 2);
+/// assert_eq!(a.index(), b.index());
+/// assert_eq!(snarl.get_node(a), None);
+/// assert_eq!(snarl.get_node(b), Some(&2));
+/// ```
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[repr(transparent)]
-pub struct NodeId(pub usize);
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NodeId {
+    index: usize,
+    generation: u64,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NodeId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "NodeId")]
+        struct Data {
+            index: usize,
+            #[serde(default)]
+            generation: u64,
+        }
+
+        /// Graphs saved before generations were added store bare indices.
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Index(usize),
+            Id(Data),
+        }
+
+        // Untagged enums need self-describing format.
+        let data = if deserializer.is_human_readable() {
+            match Compat::deserialize(deserializer)? {
+                Compat::Index(index) => Data {
+                    index,
+                    generation: 0,
+                },
+                Compat::Id(data) => data,
+            }
+        } else {
+            Data::deserialize(deserializer)?
+        };
+
+        Ok(NodeId {
+            index: data.index,
+            generation: data.generation,
+        })
+    }
+}
+
+impl NodeId {
+    /// Returns index of the node's slot.
+    /// Slots of removed nodes are reused by new nodes.
+    #[inline]
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.index
+    }
+
+    /// Returns generation of the node.
+    #[inline]
+    #[must_use]
+    pub fn generation(self) -> u64 {
+        self.generation
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -44,6 +143,43 @@ struct Node<T> {
 
     /// Flag indicating that the node is open - not collapsed.
     open: bool,
+
+    /// Generation of the node. See [`NodeId`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    generation: u64,
+
+    /// Layout measured when the node was last shown.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    layout: Option<NodeLayout>,
+}
+
+/// Measured sizes of node parts in graph space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct NodeLayout {
+    /// Size of the open node, excluding frame margin.
+    pub(crate) size: egui::Vec2,
+    pub(crate) header_height: f32,
+
+    /// Width of the header, which is the width of the collapsed node.
+    /// Zero in layouts saved before it was measured.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) header_width: f32,
+    pub(crate) body_width: f32,
+    pub(crate) footer_width: f32,
+}
+
+impl<T> Node<T> {
+    /// Returns identifier of the node stored in slot `index`.
+    fn id(&self, index: usize) -> NodeId {
+        NodeId {
+            index,
+            generation: self.generation,
+        }
+    }
 }
 
 /// Output pin identifier. Cosists of node index and pin index.
@@ -73,16 +209,54 @@ pub struct InPinId {
 /// Nodes may support multiple connections to the same input or output.
 /// But duplicate connections between same input and the same output are not allowed.
 /// Attempt to insert existing connection will be ignored.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Wire {
     out_pin: OutPinId,
     in_pin: InPinId,
 }
 
+/// Collection of wires indexed by both ends.
+///
+/// Each wire is stored twice - in the list of remotes of its output pin
+/// and in the list of remotes of its input pin.
+/// Maps are ordered by pin identifiers, so wires of a single node
+/// occupy contiguous ranges and can be found without scanning all wires.
 #[derive(Clone, Debug)]
 struct Wires {
-    wires: HashSet<Wire>,
+    /// Input pins connected to each output pin.
+    outputs: BTreeMap<OutPinId, Vec<InPinId>>,
+
+    /// Output pins connected to each input pin.
+    inputs: BTreeMap<InPinId, Vec<OutPinId>>,
+
+    /// Total number of wires.
+    len: usize,
+
+    /// Reroute points of wires in graph space, from output pin to input pin.
+    /// Wires without reroute points have no entry.
+    reroutes: BTreeMap<Wire, Vec<Pos2>>,
+}
+
+/// Serialized wire with its reroute points.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct WireRef<'a> {
+    out_pin: OutPinId,
+    in_pin: InPinId,
+    #[serde(skip_serializing_if = "<[Pos2]>::is_empty")]
+    reroutes: &'a [Pos2],
+}
+
+/// Deserialized wire with its reroute points.
+/// Wires saved without reroute points are accepted.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct WireData {
+    out_pin: OutPinId,
+    in_pin: InPinId,
+    #[serde(default)]
+    reroutes: Vec<Pos2>,
 }
 
 #[cfg(feature = "serde")]
@@ -93,9 +267,13 @@ impl serde::Serialize for Wires {
     {
         use serde::ser::SerializeSeq;
 
-        let mut seq = serializer.serialize_seq(Some(self.wires.len()))?;
-        for wire in &self.wires {
-            seq.serialize_element(&wire)?;
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        for wire in self.iter() {
+            seq.serialize_element(&WireRef {
+                out_pin: wire.out_pin,
+                in_pin: wire.in_pin,
+                reroutes: self.reroutes(&wire),
+            })?;
         }
         seq.end()
     }
@@ -110,7 +288,7 @@ impl<'de> serde::Deserialize<'de> for Wires {
         struct Visitor;
 
         impl<'de> serde::de::Visitor<'de> for Visitor {
-            type Value = HashSet<Wire>;
+            type Value = Wires;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
                 formatter.write_str("a sequence of wires")
@@ -120,69 +298,216 @@ impl<'de> serde::Deserialize<'de> for Wires {
             where
                 A: serde::de::SeqAccess<'de>,
             {
-                let mut wires = HashSet::with_hasher(egui::ahash::RandomState::new());
-                while let Some(wire) = seq.next_element()? {
+                let mut wires = Wires::new();
+                while let Some(data) = seq.next_element::<WireData>()? {
+                    let wire = Wire {
+                        out_pin: data.out_pin,
+                        in_pin: data.in_pin,
+                    };
                     wires.insert(wire);
+                    wires.set_reroutes(wire, data.reroutes);
                 }
                 Ok(wires)
             }
         }
 
-        let wires = deserializer.deserialize_seq(Visitor)?;
-        Ok(Wires { wires })
+        deserializer.deserialize_seq(Visitor)
     }
 }
 
 impl Wires {
     fn new() -> Self {
         Wires {
-            wires: HashSet::with_hasher(egui::ahash::RandomState::new()),
+            outputs: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            len: 0,
+            reroutes: BTreeMap::new(),
+        }
+    }
+
+    /// Range of all possible output pins of the node.
+    fn node_outputs(node: NodeId) -> RangeInclusive<OutPinId> {
+        OutPinId { node, output: 0 }..=OutPinId {
+            node,
+            output: usize::MAX,
+        }
+    }
+
+    /// Range of all possible input pins of the node.
+    fn node_inputs(node: NodeId) -> RangeInclusive<InPinId> {
+        InPinId { node, input: 0 }..=InPinId {
+            node,
+            input: usize::MAX,
         }
     }
 
     fn insert(&mut self, wire: Wire) -> bool {
-        self.wires.insert(wire)
+        let remotes = self.outputs.entry(wire.out_pin).or_default();
+        if remotes.contains(&wire.in_pin) {
+            return false;
+        }
+        remotes.push(wire.in_pin);
+        self.inputs
+            .entry(wire.in_pin)
+            .or_default()
+            .push(wire.out_pin);
+        self.len += 1;
+        true
     }
 
     fn remove(&mut self, wire: &Wire) -> bool {
-        self.wires.remove(wire)
+        let Some(remotes) = self.outputs.get_mut(&wire.out_pin) else {
+            return false;
+        };
+        let Some(idx) = remotes.iter().position(|pin| *pin == wire.in_pin) else {
+            return false;
+        };
+        remotes.remove(idx);
+        if remotes.is_empty() {
+            self.outputs.remove(&wire.out_pin);
+        }
+
+        let remotes = self
+            .inputs
+            .get_mut(&wire.in_pin)
+            .expect("Wire must be indexed by both pins");
+        let idx = remotes
+            .iter()
+            .position(|pin| *pin == wire.out_pin)
+            .expect("Wire must be indexed by both pins");
+        remotes.remove(idx);
+        if remotes.is_empty() {
+            self.inputs.remove(&wire.in_pin);
+        }
+
+        self.reroutes.remove(wire);
+        self.len -= 1;
+        true
     }
 
     fn drop_node(&mut self, node: NodeId) -> usize {
-        let count = self.wires.len();
-        self.wires
-            .retain(|wire| wire.out_pin.node != node && wire.in_pin.node != node);
-        count - self.wires.len()
+        let wires = self.wires_of_node(node).collect::<Vec<_>>();
+        for wire in &wires {
+            self.remove(wire);
+        }
+        wires.len()
     }
 
     fn drop_inputs(&mut self, pin: InPinId) -> usize {
-        let count = self.wires.len();
-        self.wires.retain(|wire| wire.in_pin != pin);
-        count - self.wires.len()
+        let Some(remotes) = self.inputs.remove(&pin) else {
+            return 0;
+        };
+        for &out_pin in &remotes {
+            if let Some(ins) = self.outputs.get_mut(&out_pin) {
+                ins.retain(|in_pin| *in_pin != pin);
+                if ins.is_empty() {
+                    self.outputs.remove(&out_pin);
+                }
+            }
+            self.reroutes.remove(&Wire {
+                out_pin,
+                in_pin: pin,
+            });
+        }
+        self.len -= remotes.len();
+        remotes.len()
     }
 
     fn drop_outputs(&mut self, pin: OutPinId) -> usize {
-        let count = self.wires.len();
-        self.wires.retain(|wire| wire.out_pin != pin);
-        count - self.wires.len()
+        let Some(remotes) = self.outputs.remove(&pin) else {
+            return 0;
+        };
+        for &in_pin in &remotes {
+            if let Some(outs) = self.inputs.get_mut(&in_pin) {
+                outs.retain(|out_pin| *out_pin != pin);
+                if outs.is_empty() {
+                    self.inputs.remove(&in_pin);
+                }
+            }
+            self.reroutes.remove(&Wire {
+                out_pin: pin,
+                in_pin,
+            });
+        }
+        self.len -= remotes.len();
+        remotes.len()
     }
 
     fn wired_inputs(&self, out_pin: OutPinId) -> impl Iterator<Item = InPinId> + '_ {
-        self.wires
-            .iter()
-            .filter(move |wire| wire.out_pin == out_pin)
-            .map(|wire| (wire.in_pin))
+        self.outputs.get(&out_pin).into_iter().flatten().copied()
     }
 
     fn wired_outputs(&self, in_pin: InPinId) -> impl Iterator<Item = OutPinId> + '_ {
-        self.wires
-            .iter()
-            .filter(move |wire| wire.in_pin == in_pin)
-            .map(|wire| (wire.out_pin))
+        self.inputs.get(&in_pin).into_iter().flatten().copied()
+    }
+
+    /// Returns reroute points of the wire.
+    fn reroutes(&self, wire: &Wire) -> &[Pos2] {
+        self.reroutes.get(wire).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces reroute points of the wire and returns previous ones.
+    fn set_reroutes(&mut self, wire: Wire, points: Vec<Pos2>) -> Vec<Pos2> {
+        let old = if points.is_empty() {
+            self.reroutes.remove(&wire)
+        } else {
+            self.reroutes.insert(wire, points)
+        };
+        old.unwrap_or_default()
+    }
+
+    fn contains(&self, wire: &Wire) -> bool {
+        self.outputs
+            .get(&wire.out_pin)
+            .is_some_and(|remotes| remotes.contains(&wire.in_pin))
+    }
+
+    /// Number of wires connected to node's input pins.
+    fn in_degree(&self, node: NodeId) -> usize {
+        self.inputs
+            .range(Self::node_inputs(node))
+            .map(|(_, remotes)| remotes.len())
+            .sum()
+    }
+
+    /// Number of wires connected to node's output pins.
+    fn out_degree(&self, node: NodeId) -> usize {
+        self.outputs
+            .range(Self::node_outputs(node))
+            .map(|(_, remotes)| remotes.len())
+            .sum()
+    }
+
+    /// Iterates over wires coming out of node's output pins.
+    fn node_out_wires(&self, node: NodeId) -> impl Iterator<Item = Wire> + '_ {
+        self.outputs
+            .range(Self::node_outputs(node))
+            .flat_map(|(&out_pin, remotes)| {
+                remotes.iter().map(move |&in_pin| Wire { out_pin, in_pin })
+            })
+    }
+
+    /// Iterates over wires coming into node's input pins.
+    fn node_in_wires(&self, node: NodeId) -> impl Iterator<Item = Wire> + '_ {
+        self.inputs
+            .range(Self::node_inputs(node))
+            .flat_map(|(&in_pin, remotes)| {
+                remotes.iter().map(move |&out_pin| Wire { out_pin, in_pin })
+            })
+    }
+
+    fn wires_of_node(&self, node: NodeId) -> impl Iterator<Item = Wire> + '_ {
+        // Wires from node to itself are yielded only once - as outgoing wires.
+        self.node_out_wires(node).chain(
+            self.node_in_wires(node)
+                .filter(move |wire| wire.out_pin.node != node),
+        )
     }
 
     fn iter(&self) -> impl Iterator<Item = Wire> + '_ {
-        self.wires.iter().copied()
+        self.outputs.iter().flat_map(|(&out_pin, remotes)| {
+            remotes.iter().map(move |&in_pin| Wire { out_pin, in_pin })
+        })
     }
 }
 
@@ -191,12 +516,82 @@ impl Wires {
 /// It holds graph state - positioned nodes and wires between their pins.
 /// It can be rendered using [`Snarl::show`].
 #[derive(Clone, Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SnarlData<T>")
+)]
 pub struct Snarl<T> {
     // #[cfg_attr(feature = "serde", serde(with = "serde_nodes"))]
     nodes: Slab<Node<T>>,
     draw_order: Vec<NodeId>,
     wires: Wires,
+
+    /// Generation of the next inserted node.
+    /// Always greater than generations of existing nodes.
+    next_generation: u64,
+
+    /// Selected nodes in order of selection. Not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    selected: Vec<NodeId>,
+
+    /// Undo/redo history. Not serialized.
+    #[cfg_attr(feature = "serde", serde(skip, default = "Option::default"))]
+    history: Option<Box<History<T>>>,
+}
+
+/// Deserialized Snarl before it is checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SnarlData<T> {
+    nodes: Slab<Node<T>>,
+    #[serde(default)]
+    draw_order: Vec<NodeId>,
+    wires: Wires,
+    #[serde(default)]
+    next_generation: u64,
+}
+
+#[cfg(feature = "serde")]
+impl<T> From<SnarlData<T>> for Snarl<T> {
+    fn from(data: SnarlData<T>) -> Self {
+        let SnarlData {
+            nodes,
+            mut draw_order,
+            wires,
+            next_generation,
+        } = data;
+
+        // Saved data may be old or tampered with, so generation of the next node
+        // is raised above existing ones to keep stale identifiers detectable.
+        let next_generation = nodes
+            .iter()
+            .map(|(_, node)| node.generation + 1)
+            .fold(next_generation, u64::max);
+
+        // Draw order must list every node exactly once.
+        let mut seen = HashSet::default();
+        draw_order.retain(|&id| {
+            nodes
+                .get(id.index)
+                .is_some_and(|node| node.generation == id.generation)
+                && seen.insert(id)
+        });
+        for (idx, node) in nodes.iter() {
+            if !seen.contains(&node.id(idx)) {
+                draw_order.push(node.id(idx));
+            }
+        }
+
+        Snarl {
+            nodes,
+            draw_order,
+            wires,
+            next_generation,
+            selected: Vec::new(),
+            history: None,
+        }
+    }
 }
 
 impl<T> Snarl<T> {
@@ -214,6 +609,9 @@ impl<T> Snarl<T> {
             nodes: Slab::new(),
             draw_order: Vec::new(),
             wires: Wires::new(),
+            next_generation: 0,
+            selected: Vec::new(),
+            history: None,
         }
     }
 
@@ -225,16 +623,12 @@ impl<T> Snarl<T> {
     /// ```
     /// # use egui_snarl::Snarl;
     /// let mut snarl = Snarl::<()>::new();
-    /// snarl.insert_node(());
+    /// snarl.insert_node(egui::pos2(0.0, 0.0), ());
     /// ```
     pub fn insert_node(&mut self, pos: egui::Pos2, node: T) -> NodeId {
-        let idx = self.nodes.insert(Node {
-            value: node,
-            pos,
-            open: true,
-        });
-        let id = NodeId(idx);
+        let id = self.alloc_node(node, pos, true);
         self.draw_order.push(id);
+        self.record(Edit::RemoveNode { node: id });
         id
     }
 
@@ -246,16 +640,12 @@ impl<T> Snarl<T> {
     /// ```
     /// # use egui_snarl::Snarl;
     /// let mut snarl = Snarl::<()>::new();
-    /// snarl.insert_node(());
+    /// snarl.add_node_collapsed(egui::pos2(0.0, 0.0), ());
     /// ```
     pub fn add_node_collapsed(&mut self, pos: egui::Pos2, node: T) -> NodeId {
-        let idx = self.nodes.insert(Node {
-            value: node,
-            pos,
-            open: false,
-        });
-        let id = NodeId(idx);
+        let id = self.alloc_node(node, pos, false);
         self.draw_order.push(id);
+        self.record(Edit::RemoveNode { node: id });
         id
     }
 
@@ -266,7 +656,21 @@ impl<T> Snarl<T> {
     /// Panics if the node does not exist.
     #[track_caller]
     pub fn open_node(&mut self, node: NodeId, open: bool) {
-        self.nodes[node.0].open = open;
+        let old = std::mem::replace(&mut self.node_mut(node).open, open);
+        if old != open {
+            self.record(Edit::Open { node, open: old });
+        }
+    }
+
+    /// Opens or collapses a node.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if the node does not exist.
+    pub fn try_open_node(&mut self, node: NodeId, open: bool) -> Result<(), SnarlError> {
+        self.check_node(node)?;
+        self.open_node(node, open);
+        Ok(())
     }
 
     /// Removes a node from the Snarl.
@@ -281,18 +685,47 @@ impl<T> Snarl<T> {
     /// ```
     /// # use egui_snarl::Snarl;
     /// let mut snarl = Snarl::<()>::new();
-    /// let node = snarl.insert_node(());
+    /// let node = snarl.insert_node(egui::pos2(0.0, 0.0), ());
     /// snarl.remove_node(node);
     /// ```
     #[track_caller]
     pub fn remove_node(&mut self, idx: NodeId) -> T {
-        let value = self.nodes.remove(idx.0).value;
+        let node = self.node(idx);
+        if let Some(history) = &self.history {
+            let edit = Edit::RestoreNode {
+                node: idx,
+                value: (history.clone)(&node.value),
+                pos: node.pos,
+                open: node.open,
+                layout: node.layout,
+                wires: self
+                    .wires
+                    .wires_of_node(idx)
+                    .map(|wire| (wire, self.wires.reroutes(&wire).to_vec()))
+                    .collect(),
+            };
+            self.record(edit);
+        }
+
+        let value = self.nodes.remove(idx.index).value;
         self.wires.drop_node(idx);
         let order = self.draw_order.iter().position(|&i| i == idx).unwrap();
         self.draw_order.remove(order);
+        self.selected.retain(|&i| i != idx);
         value
     }
 
+    /// Removes a node from the Snarl.
+    /// Returns the removed node.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if the node does not exist.
+    pub fn try_remove_node(&mut self, idx: NodeId) -> Result<T, SnarlError> {
+        self.check_node(idx)?;
+        Ok(self.remove_node(idx))
+    }
+
     /// Connects two nodes.
     /// Returns true if the connection was successful.
     /// Returns false if the connection already exists.
@@ -302,14 +735,62 @@ impl<T> Snarl<T> {
     /// Panics if either node does not exist.
     #[track_caller]
     pub fn connect(&mut self, from: OutPinId, to: InPinId) -> bool {
-        assert!(self.nodes.contains(from.node.0));
-        assert!(self.nodes.contains(to.node.0));
+        assert!(self.contains_node(from.node));
+        assert!(self.contains_node(to.node));
 
         let wire = Wire {
             out_pin: from,
             in_pin: to,
         };
-        self.wires.insert(wire)
+        let inserted = self.wires.insert(wire);
+        if inserted {
+            self.record(Edit::Disconnect { wire });
+        }
+        inserted
+    }
+
+    /// Connects two nodes.
+    ///
+    /// Same as [`Snarl::connect`], but returns an error instead of panicking.
+    /// Unlike [`Snarl::connect`] this method rejects wires from a node to itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if either node does not exist,
+    /// [`SnarlError::SelfLoop`] if both pins belong to the same node
+    /// and [`SnarlError::DuplicateWire`] if the pins are already connected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, NodeId, OutPinId, Snarl, SnarlError};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    ///
+    /// let from = OutPinId { node: a, output: 0 };
+    /// let to = InPinId { node: b, input: 0 };
+    /// assert_eq!(snarl.try_connect(from, to), Ok(()));
+    /// assert_eq!(snarl.try_connect(from, to), Err(SnarlError::DuplicateWire { from, to }));
+    ///
+    /// let to = InPinId { node: a, input: 0 };
+    /// assert_eq!(snarl.try_connect(from, to), Err(SnarlError::SelfLoop { from, to }));
+    ///
+    /// snarl.remove_node(b);
+    /// let to = InPinId { node: b, input: 0 };
+    /// assert_eq!(snarl.try_connect(from, to), Err(SnarlError::UnknownNode(b)));
+    /// ```
+    pub fn try_connect(&mut self, from: OutPinId, to: InPinId) -> Result<(), SnarlError> {
+        self.check_node(from.node)?;
+        self.check_node(to.node)?;
+
+        if from.node == to.node {
+            return Err(SnarlError::SelfLoop { from, to });
+        }
+        if !self.connect(from, to) {
+            return Err(SnarlError::DuplicateWire { from, to });
+        }
+        Ok(())
     }
 
     /// Disconnects two nodes.
@@ -320,15 +801,32 @@ impl<T> Snarl<T> {
     /// Panics if either node does not exist.
     #[track_caller]
     pub fn disconnect(&mut self, from: OutPinId, to: InPinId) -> bool {
-        assert!(self.nodes.contains(from.node.0));
-        assert!(self.nodes.contains(to.node.0));
+        assert!(self.contains_node(from.node));
+        assert!(self.contains_node(to.node));
 
         let wire = Wire {
             out_pin: from,
             in_pin: to,
         };
 
-        self.wires.remove(&wire)
+        let reroutes = self.wires.reroutes(&wire).to_vec();
+        let removed = self.wires.remove(&wire);
+        if removed {
+            self.record(Edit::Connect { wire, reroutes });
+        }
+        removed
+    }
+
+    /// Disconnects two nodes.
+    /// Returns true if the connection was removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if either node does not exist.
+    pub fn try_disconnect(&mut self, from: OutPinId, to: InPinId) -> Result<bool, SnarlError> {
+        self.check_node(from.node)?;
+        self.check_node(to.node)?;
+        Ok(self.disconnect(from, to))
     }
 
     /// Removes all connections to the node's pin.
@@ -340,10 +838,34 @@ impl<T> Snarl<T> {
     /// Panics if the node does not exist.
     #[track_caller]
     pub fn drop_inputs(&mut self, pin: InPinId) -> usize {
-        assert!(self.nodes.contains(pin.node.0));
+        assert!(self.contains_node(pin.node));
+        if let Some(history) = &mut self.history {
+            for out_pin in self.wires.wired_outputs(pin) {
+                let wire = Wire {
+                    out_pin,
+                    in_pin: pin,
+                };
+                history.record(Edit::Connect {
+                    wire,
+                    reroutes: self.wires.reroutes(&wire).to_vec(),
+                });
+            }
+        }
         self.wires.drop_inputs(pin)
     }
 
+    /// Removes all connections to the node's pin.
+    ///
+    /// Returns number of removed connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if the node does not exist.
+    pub fn try_drop_inputs(&mut self, pin: InPinId) -> Result<usize, SnarlError> {
+        self.check_node(pin.node)?;
+        Ok(self.drop_inputs(pin))
+    }
+
     /// Removes all connections from the node's pin.
     /// Returns number of removed connections.
     ///
@@ -352,24 +874,58 @@ impl<T> Snarl<T> {
     /// Panics if the node does not exist.
     #[track_caller]
     pub fn drop_outputs(&mut self, pin: OutPinId) -> usize {
-        assert!(self.nodes.contains(pin.node.0));
+        assert!(self.contains_node(pin.node));
+        if let Some(history) = &mut self.history {
+            for in_pin in self.wires.wired_inputs(pin) {
+                let wire = Wire {
+                    out_pin: pin,
+                    in_pin,
+                };
+                history.record(Edit::Connect {
+                    wire,
+                    reroutes: self.wires.reroutes(&wire).to_vec(),
+                });
+            }
+        }
         self.wires.drop_outputs(pin)
     }
 
+    /// Removes all connections from the node's pin.
+    /// Returns number of removed connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if the node does not exist.
+    pub fn try_drop_outputs(&mut self, pin: OutPinId) -> Result<usize, SnarlError> {
+        self.check_node(pin.node)?;
+        Ok(self.drop_outputs(pin))
+    }
+
+    /// Checks that the node exists.
+    fn check_node(&self, node: NodeId) -> Result<(), SnarlError> {
+        if self.contains_node(node) {
+            Ok(())
+        } else {
+            Err(SnarlError::UnknownNode(node))
+        }
+    }
+
     /// Returns reference to the node.
+    /// Returns `None` if the node does not exist.
     #[must_use]
     pub fn get_node(&self, idx: NodeId) -> Option<&T> {
-        match self.nodes.get(idx.0) {
-            Some(node) => Some(&node.value),
-            None => None,
+        match self.nodes.get(idx.index) {
+            Some(node) if node.generation == idx.generation => Some(&node.value),
+            _ => None,
         }
     }
 
     /// Returns mutable reference to the node.
+    /// Returns `None` if the node does not exist.
     pub fn get_node_mut(&mut self, idx: NodeId) -> Option<&mut T> {
-        match self.nodes.get_mut(idx.0) {
-            Some(node) => Some(&mut node.value),
-            None => None,
+        match self.nodes.get_mut(idx.index) {
+            Some(node) if node.generation == idx.generation => Some(&mut node.value),
+            _ => None,
         }
     }
 
@@ -429,6 +985,82 @@ impl<T> Snarl<T> {
         }
     }
 
+    /// Returns rectangle of the node in graph space, excluding frame margin.
+    /// Collapsed node is as tall as its header.
+    ///
+    /// Node sizes are measured when the Snarl is shown, so this returns `None`
+    /// if the node does not exist or was never shown.
+    /// Measured sizes are serialized together with nodes.
+    #[must_use]
+    pub fn node_rect(&self, node: NodeId) -> Option<egui::Rect> {
+        let entry = self.nodes.get(node.index)?;
+        if entry.generation != node.generation {
+            return None;
+        }
+        let layout = entry.layout?;
+        let size = if entry.open || layout.header_width <= 0.0 {
+            layout.size
+        } else {
+            egui::vec2(layout.header_width, layout.header_height)
+        };
+        Some(egui::Rect::from_min_size(entry.pos, size))
+    }
+
+    /// Returns selected nodes in order they were selected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::Snarl;
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    ///
+    /// snarl.select_node(b);
+    /// snarl.select_node(a);
+    /// assert_eq!(snarl.selected_nodes(), [b, a]);
+    ///
+    /// snarl.remove_node(b);
+    /// assert_eq!(snarl.selected_nodes(), [a]);
+    /// ```
+    #[must_use]
+    pub fn selected_nodes(&self) -> &[NodeId] {
+        &self.selected
+    }
+
+    /// Checks if the node is selected.
+    #[must_use]
+    pub fn is_node_selected(&self, node: NodeId) -> bool {
+        self.selected.contains(&node)
+    }
+
+    /// Adds the node to the selection.
+    /// Does nothing if the node does not exist or is already selected.
+    pub fn select_node(&mut self, node: NodeId) {
+        if self.contains_node(node) && !self.selected.contains(&node) {
+            self.selected.push(node);
+        }
+    }
+
+    /// Removes the node from the selection.
+    pub fn deselect_node(&mut self, node: NodeId) {
+        self.selected.retain(|&n| n != node);
+    }
+
+    /// Replaces the selection with given nodes.
+    /// Nodes that do not exist are skipped.
+    pub fn set_selection(&mut self, nodes: impl IntoIterator<Item = NodeId>) {
+        self.selected.clear();
+        for node in nodes {
+            self.select_node(node);
+        }
+    }
+
+    /// Deselects all nodes.
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
+    }
+
     /// Returns input pin of the node.
     #[must_use]
     pub fn in_pin(&self, pin: InPinId) -> InPin {
@@ -440,15 +1072,343 @@ impl<T> Snarl<T> {
     pub fn out_pin(&self, pin: OutPinId) -> OutPin {
         OutPin::new(self, pin)
     }
+
+    /// Iterates over all wires in the Snarl.
+    /// Each wire is yielded as pair of output and input pin identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    ///
+    /// let from = OutPinId { node: a, output: 0 };
+    /// let to = InPinId { node: b, input: 0 };
+    /// snarl.connect(from, to);
+    ///
+    /// assert_eq!(snarl.wires().collect::<Vec<_>>(), [(from, to)]);
+    /// ```
+    pub fn wires(&self) -> impl Iterator<Item = (OutPinId, InPinId)> + '_ {
+        self.wires.iter().map(|wire| (wire.out_pin, wire.in_pin))
+    }
+
+    /// Iterates over all wires connected to any pin of the node.
+    /// This includes both wires coming into node's inputs
+    /// and wires going out of node's outputs.
+    pub fn wires_of_node(&self, node: NodeId) -> impl Iterator<Item = (OutPinId, InPinId)> + '_ {
+        self.wires
+            .wires_of_node(node)
+            .map(|wire| (wire.out_pin, wire.in_pin))
+    }
+
+    /// Checks if output pin is connected to the input pin.
+    #[must_use]
+    pub fn is_connected(&self, from: OutPinId, to: InPinId) -> bool {
+        self.wires.contains(&Wire {
+            out_pin: from,
+            in_pin: to,
+        })
+    }
+
+    /// Returns reroute points of the wire in graph space,
+    /// ordered from the output pin to the input pin.
+    ///
+    /// Reroute points only change how the wire is drawn.
+    /// Wire still connects the output pin directly to the input pin,
+    /// so graph queries like [`InPin::remotes`] or [`Snarl::upstream_nodes`] are not affected.
+    ///
+    /// Returns empty slice if the wire has no reroute points or does not exist.
+    #[must_use]
+    pub fn wire_reroutes(&self, from: OutPinId, to: InPinId) -> &[Pos2] {
+        self.wires.reroutes(&Wire {
+            out_pin: from,
+            in_pin: to,
+        })
+    }
+
+    /// Inserts reroute point into the wire at `index`,
+    /// shifting following points towards the input pin.
+    /// Returns false if the pins are not connected or `index` is greater than number of points.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(300.0, 0.0), ());
+    ///
+    /// let from = OutPinId { node: a, output: 0 };
+    /// let to = InPinId { node: b, input: 0 };
+    /// snarl.connect(from, to);
+    ///
+    /// assert!(snarl.insert_reroute(from, to, 0, egui::pos2(100.0, 50.0)));
+    /// assert!(snarl.insert_reroute(from, to, 1, egui::pos2(200.0, 50.0)));
+    /// assert_eq!(snarl.wire_reroutes(from, to), [egui::pos2(100.0, 50.0), egui::pos2(200.0, 50.0)]);
+    ///
+    /// // Reroute points do not change the graph.
+    /// assert_eq!(snarl.in_pin(to).remotes, [from]);
+    ///
+    /// snarl.disconnect(from, to);
+    /// assert!(snarl.wire_reroutes(from, to).is_empty());
+    /// ```
+    pub fn insert_reroute(&mut self, from: OutPinId, to: InPinId, index: usize, pos: Pos2) -> bool {
+        let wire = Wire {
+            out_pin: from,
+            in_pin: to,
+        };
+        if !self.wires.contains(&wire) || index > self.wires.reroutes(&wire).len() {
+            return false;
+        }
+        self.edit_reroutes(wire, |points| points.insert(index, pos));
+        true
+    }
+
+    /// Moves reroute point of the wire to new position in graph space.
+    /// Returns false if the wire has no such point.
+    pub fn move_reroute(&mut self, from: OutPinId, to: InPinId, index: usize, pos: Pos2) -> bool {
+        let wire = Wire {
+            out_pin: from,
+            in_pin: to,
+        };
+        if index >= self.wires.reroutes(&wire).len() {
+            return false;
+        }
+        self.edit_reroutes(wire, |points| points[index] = pos);
+        true
+    }
+
+    /// Removes reroute point from the wire.
+    /// Returns removed point or `None` if the wire has no such point.
+    pub fn remove_reroute(&mut self, from: OutPinId, to: InPinId, index: usize) -> Option<Pos2> {
+        let wire = Wire {
+            out_pin: from,
+            in_pin: to,
+        };
+        if index >= self.wires.reroutes(&wire).len() {
+            return None;
+        }
+        Some(self.edit_reroutes(wire, |points| points.remove(index)))
+    }
+
+    /// Removes wires connected to pins that nodes do not have.
+    ///
+    /// `pins` returns number of input and output pins of the node.
+    /// This is useful after loading saved graph or changing nodes
+    /// in a way that removes some of their pins.
+    ///
+    /// Returns removed wires.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), 1);
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 1 });
+    ///
+    /// // Node has as many inputs as its value.
+    /// let removed = snarl.prune_wires(|&value| (value, 1));
+    /// assert_eq!(removed, [(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 1 })]);
+    /// assert_eq!(snarl.wires().count(), 1);
+    /// ```
+    pub fn prune_wires(
+        &mut self,
+        mut pins: impl FnMut(&T) -> (usize, usize),
+    ) -> Vec<(OutPinId, InPinId)> {
+        let mut counts = HashMap::default();
+        for (idx, node) in self.nodes.iter() {
+            counts.insert(node.id(idx), pins(&node.value));
+        }
+
+        let dangling = self
+            .wires
+            .iter()
+            .filter(|wire| {
+                let out_valid = counts
+                    .get(&wire.out_pin.node)
+                    .is_some_and(|&(_, outputs)| wire.out_pin.output < outputs);
+                let in_valid = counts
+                    .get(&wire.in_pin.node)
+                    .is_some_and(|&(inputs, _)| wire.in_pin.input < inputs);
+                !out_valid || !in_valid
+            })
+            .collect::<Vec<_>>();
+
+        for &wire in &dangling {
+            let reroutes = self.wires.reroutes(&wire).to_vec();
+            self.wires.remove(&wire);
+            self.record(Edit::Connect { wire, reroutes });
+        }
+
+        dangling
+            .into_iter()
+            .map(|wire| (wire.out_pin, wire.in_pin))
+            .collect()
+    }
+
+    /// Returns number of wires connected to node's input pins.
+    #[must_use]
+    pub fn in_degree(&self, node: NodeId) -> usize {
+        self.wires.in_degree(node)
+    }
+
+    /// Returns number of wires connected to node's output pins.
+    #[must_use]
+    pub fn out_degree(&self, node: NodeId) -> usize {
+        self.wires.out_degree(node)
+    }
+
+    /// Returns set of nodes that feed the node, directly or through other nodes.
+    ///
+    /// The node itself is included only if it is part of a cycle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{InPinId, OutPinId, Snarl};
+    /// let mut snarl = Snarl::<()>::new();
+    /// let a = snarl.insert_node(egui::pos2(0.0, 0.0), ());
+    /// let b = snarl.insert_node(egui::pos2(100.0, 0.0), ());
+    /// let c = snarl.insert_node(egui::pos2(200.0, 0.0), ());
+    ///
+    /// snarl.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+    /// snarl.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+    ///
+    /// let upstream = snarl.upstream_nodes(c);
+    /// assert!(upstream.contains(&a) && upstream.contains(&b));
+    /// assert!(snarl.downstream_nodes(c).is_empty());
+    /// ```
+    #[must_use]
+    pub fn upstream_nodes(&self, node: NodeId) -> HashSet<NodeId> {
+        self.reachable_nodes(node, true)
+    }
+
+    /// Returns set of nodes fed by the node, directly or through other nodes.
+    ///
+    /// The node itself is included only if it is part of a cycle.
+    #[must_use]
+    pub fn downstream_nodes(&self, node: NodeId) -> HashSet<NodeId> {
+        self.reachable_nodes(node, false)
+    }
+
+    /// Checks if the node exists.
+    /// Returns `false` for identifiers of removed nodes.
+    #[must_use]
+    pub fn contains_node(&self, node: NodeId) -> bool {
+        self.nodes
+            .get(node.index)
+            .is_some_and(|entry| entry.generation == node.generation)
+    }
+
+    /// Returns the node entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node does not exist.
+    #[track_caller]
+    fn node(&self, node: NodeId) -> &Node<T> {
+        match self.nodes.get(node.index) {
+            Some(entry) if entry.generation == node.generation => entry,
+            _ => panic!("node {node} does not exist"),
+        }
+    }
+
+    /// Returns mutable node entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node does not exist.
+    #[track_caller]
+    fn node_mut(&mut self, node: NodeId) -> &mut Node<T> {
+        match self.nodes.get_mut(node.index) {
+            Some(entry) if entry.generation == node.generation => entry,
+            _ => panic!("node {node} does not exist"),
+        }
+    }
+
+    /// Stores new node with fresh generation.
+    /// Does not add it to draw order nor records it in history.
+    fn alloc_node(&mut self, value: T, pos: Pos2, open: bool) -> NodeId {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let index = self.nodes.insert(Node {
+            value,
+            pos,
+            open,
+            generation,
+            layout: None,
+        });
+        NodeId { index, generation }
+    }
+
+    /// Records edit that reverts a mutation, if history is enabled.
+    fn record(&mut self, edit: Edit<T>) {
+        if let Some(history) = &mut self.history {
+            history.record(edit);
+        }
+    }
+
+    /// Moves node by the given delta in graph space.
+    fn move_node(&mut self, node: NodeId, delta: egui::Vec2) {
+        let pos = &mut self.node_mut(node).pos;
+        let old = *pos;
+        *pos += delta;
+        self.record(Edit::Move { node, pos: old });
+    }
+
+    /// Changes reroute points of existing wire.
+    fn edit_reroutes<R>(&mut self, wire: Wire, f: impl FnOnce(&mut Vec<Pos2>) -> R) -> R {
+        let mut points = self.wires.set_reroutes(wire, Vec::new());
+        let old = self.history.is_some().then(|| points.clone());
+        let result = f(&mut points);
+        if let Some(old) = old {
+            self.record(Edit::Reroute { wire, points: old });
+        }
+        self.wires.set_reroutes(wire, points);
+        result
+    }
+
+    /// Walks wires starting from the node.
+    /// Walks against wire direction if `upstream` is true.
+    fn reachable_nodes(&self, node: NodeId, upstream: bool) -> HashSet<NodeId> {
+        let mut visited = HashSet::with_hasher(egui::ahash::RandomState::new());
+        let mut stack = vec![node];
+
+        while let Some(next) = stack.pop() {
+            if upstream {
+                for wire in self.wires.node_in_wires(next) {
+                    if visited.insert(wire.out_pin.node) {
+                        stack.push(wire.out_pin.node);
+                    }
+                }
+            } else {
+                for wire in self.wires.node_out_wires(next) {
+                    if visited.insert(wire.in_pin.node) {
+                        stack.push(wire.in_pin.node);
+                    }
+                }
+            }
+        }
+
+        visited
+    }
 }
 
+/// Indexing panics if the node does not exist.
+/// Use [`Snarl::get_node`] and [`Snarl::get_node_mut`] for fallible access.
 impl<T> Index<NodeId> for Snarl<T> {
     type Output = T;
 
     #[inline]
     #[track_caller]
     fn index(&self, idx: NodeId) -> &Self::Output {
-        &self.nodes[idx.0].value
+        &self.node(idx).value
     }
 }
 
@@ -456,7 +1416,7 @@ impl<T> IndexMut<NodeId> for Snarl<T> {
     #[inline]
     #[track_caller]
     fn index_mut(&mut self, idx: NodeId) -> &mut Self::Output {
-        &mut self.nodes[idx.0].value
+        &mut self.node_mut(idx).value
     }
 }
 
@@ -571,12 +1531,12 @@ impl<'a, T> Iterator for NodesIdsIter<'a, T> {
 
     fn next(&mut self) -> Option<(NodeId, &'a T)> {
         let (idx, node) = self.nodes.next()?;
-        Some((NodeId(idx), &node.value))
+        Some((node.id(idx), &node.value))
     }
 
     fn nth(&mut self, n: usize) -> Option<(NodeId, &'a T)> {
         let (idx, node) = self.nodes.nth(n)?;
-        Some((NodeId(idx), &node.value))
+        Some((node.id(idx), &node.value))
     }
 }
 
@@ -595,12 +1555,12 @@ impl<'a, T> Iterator for NodesIdsIterMut<'a, T> {
 
     fn next(&mut self) -> Option<(NodeId, &'a mut T)> {
         let (idx, node) = self.nodes.next()?;
-        Some((NodeId(idx), &mut node.value))
+        Some((node.id(idx), &mut node.value))
     }
 
     fn nth(&mut self, n: usize) -> Option<(NodeId, &'a mut T)> {
         let (idx, node) = self.nodes.nth(n)?;
-        Some((NodeId(idx), &mut node.value))
+        Some((node.id(idx), &mut node.value))
     }
 }
 
@@ -619,12 +1579,12 @@ impl<'a, T> Iterator for NodesPosIdsIter<'a, T> {
 
     fn next(&mut self) -> Option<(NodeId, Pos2, &'a T)> {
         let (idx, node) = self.nodes.next()?;
-        Some((NodeId(idx), node.pos, &node.value))
+        Some((node.id(idx), node.pos, &node.value))
     }
 
     fn nth(&mut self, n: usize) -> Option<(NodeId, Pos2, &'a T)> {
         let (idx, node) = self.nodes.nth(n)?;
-        Some((NodeId(idx), node.pos, &node.value))
+        Some((node.id(idx), node.pos, &node.value))
     }
 }
 
@@ -643,12 +1603,12 @@ impl<'a, T> Iterator for NodesPosIdsIterMut<'a, T> {
 
     fn next(&mut self) -> Option<(NodeId, Pos2, &'a mut T)> {
         let (idx, node) = self.nodes.next()?;
-        Some((NodeId(idx), node.pos, &mut node.value))
+        Some((node.id(idx), node.pos, &mut node.value))
     }
 
     fn nth(&mut self, n: usize) -> Option<(NodeId, Pos2, &'a mut T)> {
         let (idx, node) = self.nodes.nth(n)?;
-        Some((NodeId(idx), node.pos, &mut node.value))
+        Some((node.id(idx), node.pos, &mut node.value))
     }
 }
 
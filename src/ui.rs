@@ -1,33 +1,45 @@
 //! This module provides functionality for showing [`Snarl`] graph in [`Ui`].
 
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
 
 use egui::{
-    collapsing_header::paint_default_icon, epaint::Shadow, pos2, vec2, Align, Color32, Frame, Id,
-    Layout, Modifiers, PointerButton, Pos2, Rect, Sense, Shape, Stroke, Style, Ui, Vec2,
+    collapsing_header::paint_default_icon, epaint::Shadow, pos2, vec2, Align, Color32, Context,
+    Event, Frame, Id, Key, KeyboardShortcut, Layout, Modifiers, PointerButton, Pos2, Rect, Sense,
+    Shape, Stroke, Style, Ui, Vec2,
 };
 
-use crate::{InPin, InPinId, Node, NodeId, OutPin, OutPinId, Snarl};
+use crate::{InPin, InPinId, Node, NodeId, NodeLayout, OutPin, OutPinId, Snarl, SnarlError, Wire};
 
 mod background_pattern;
+mod effect;
 mod pin;
+mod response;
 mod state;
 mod viewer;
+mod viewport;
 mod wire;
 mod zoom;
 
 use self::{
-    pin::{draw_pin, AnyPin},
-    state::{NewWires, NodeState, SnarlState},
-    wire::{draw_wire, hit_wire, mix_colors},
+    pin::draw_pin,
+    state::{fit_scale, push_pending_wires, take_pending_wires, NodeState, SnarlState},
+    viewport::ViewRequest,
+    wire::{draw_wire, hit_wire, mix_colors, FlowPaint, WirePaint},
     zoom::Zoom,
 };
 
 pub use self::{
     background_pattern::{BackgroundPattern, CustomBackground, Grid, Viewport},
-    pin::{CustomPinShape, PinInfo, PinShape},
+    effect::{Effect, Effects, SnarlClosure},
+    pin::{AnyPin, CustomPinShape, PinInfo, PinShape},
+    response::SnarlResponse,
+    state::NewWires,
     viewer::SnarlViewer,
-    wire::WireLayer,
+    viewport::SnarlViewport,
+    wire::{WireColor, WireFlow, WireInfo, WireLayer, WireStyle},
 };
 
 /// Style for rendering Snarl.
@@ -51,6 +63,10 @@ pub struct SnarlStyle {
     /// Layer where wires are rendered.
     pub wire_layer: WireLayer,
 
+    /// Shape of wires.
+    /// Viewer may override it for individual wires with [`SnarlViewer::wire_style`].
+    pub wire_style: WireStyle,
+
     /// Additional blank space for dragging node by header.
     pub header_drag_space: Option<Vec2>,
 
@@ -91,11 +107,28 @@ pub struct SnarlStyle {
     /// unless layering of header fill color with node fill color is desired.
     pub header_frame: Option<Frame>,
 
+    /// Stroke drawn around selected nodes.
+    /// Defaults to `ui.visuals().selection.stroke`.
+    pub select_stroke: Option<Stroke>,
+
+    /// Fill of the rectangle used to select nodes.
+    /// Defaults to `ui.visuals().selection.bg_fill` with reduced opacity.
+    pub select_rect_fill: Option<Color32>,
+
+    /// Stroke of the rectangle used to select nodes.
+    /// Defaults to `ui.visuals().selection.stroke`.
+    pub select_rect_stroke: Option<Stroke>,
+
+    /// Whether wire flow set by [`SnarlViewer::wire_info`] is animated.
+    /// If false, flowing wires are drawn as regular wires.
+    pub animate_wires: bool,
+
     #[doc(hidden)]
     /// Do not access other than with .., here to emulate `#[non_exhaustive(pub)]`
     pub _non_exhaustive: (),
 
-    pub centering: bool
+    /// Whether double-clicking the background fits all nodes into the viewport.
+    pub centering: bool,
 }
 
 impl SnarlStyle {
@@ -109,6 +142,7 @@ impl SnarlStyle {
             downscale_wire_frame: false,
             upscale_wire_frame: true,
             wire_layer: WireLayer::BehindNodes,
+            wire_style: WireStyle::Bezier,
             header_drag_space: None,
             collapsible: true,
 
@@ -122,8 +156,13 @@ impl SnarlStyle {
             node_frame: None,
             header_frame: None,
 
+            select_stroke: None,
+            select_rect_fill: None,
+            select_rect_stroke: None,
+            animate_wires: true,
+
             _non_exhaustive: (),
-            centering: true
+            centering: true,
         }
     }
 }
@@ -135,6 +174,37 @@ impl Default for SnarlStyle {
     }
 }
 
+/// Wires to connect when dragged wires are dropped on a pin
+/// and existing wires they replace.
+struct WiresDrop {
+    connect: Vec<(OutPinId, InPinId)>,
+    replace: Vec<Wire>,
+}
+
+/// Greys out pin that dragged wires can't be connected to
+/// and highlights pin they can be connected to.
+fn mark_compatibility(pin: PinInfo, compatible: Option<bool>, style: &Style) -> PinInfo {
+    match compatible {
+        None => pin,
+        Some(true) => {
+            let stroke = style.visuals.selection.stroke;
+            let width = stroke.width.max(pin.stroke.width);
+            pin.with_stroke(Stroke::new(width, stroke.color))
+        }
+        Some(false) => PinInfo {
+            fill: pin.fill.gamma_multiply(0.25),
+            stroke: Stroke::new(pin.stroke.width, pin.stroke.color.gamma_multiply(0.25)),
+            ..pin
+        },
+    }
+}
+
+/// Offset of duplicated nodes from the original ones in graph space.
+const DUPLICATE_OFFSET: Vec2 = vec2(20.0, 20.0);
+
+/// Margin around nodes fitted into the viewport in graph space.
+const FIT_MARGIN: f32 = 20.0;
+
 struct Input {
     hover_pos: Option<Pos2>,
     scroll_delta: f32,
@@ -146,9 +216,15 @@ struct Input {
 struct DrawNodeResponse {
     node_moved: Option<(NodeId, Vec2)>,
     node_to_top: Option<NodeId>,
+    node_clicked: Option<NodeId>,
+    node_double_clicked: Option<NodeId>,
+    node_drag_started: Option<NodeId>,
+    node_drag_released: Option<NodeId>,
+    node_hovered: bool,
+    in_rect_selection: bool,
+    layout: Option<NodeLayout>,
     drag_released: bool,
     pin_hovered: Option<AnyPin>,
-    center: Vec2
 }
 
 impl<T> Snarl<T> {
@@ -163,8 +239,18 @@ impl<T> Snarl<T> {
     }
 
     /// Render [`Snarl`] using given viewer and style into the [`Ui`].
-
-    pub fn show<V>(&mut self, viewer: &mut V, style: &SnarlStyle, id_source: impl Hash, ui: &mut Ui)
+    ///
+    /// `id_source` must be unique among Snarls shown in the same parent [`Ui`].
+    /// Resulting id is returned in [`SnarlResponse::id`] and is used to control the viewport with [`SnarlViewport`].
+    ///
+    /// Returns [`SnarlResponse`] describing what happened with the graph during the frame.
+    pub fn show<V>(
+        &mut self,
+        viewer: &mut V,
+        style: &SnarlStyle,
+        id_source: impl Hash,
+        ui: &mut Ui,
+    ) -> SnarlResponse
     where
         V: SnarlViewer<T>,
     {
@@ -172,9 +258,47 @@ impl<T> Snarl<T> {
 
         let mut node_moved = None;
         let mut node_to_top = None;
+        let mut node_clicked = None;
+        let mut node_double_clicked = None;
+        let mut node_drag_started = None;
+        let mut node_drag_released = None;
+        let mut reroute_moved = None;
+        let mut reroute_inserted = None;
+        let mut reroute_removed = None;
+        let mut hovered_node = None;
+        let mut hovered_pin = None;
+        let mut wire_hovered = None;
+        let mut pointer_pos = None;
+        let mut node_layouts = Vec::new();
+        let mut rect_selected = None;
+        let mut bg_clicked = false;
+        let mut pending_drops = Vec::new();
 
         let snarl_id = ui.make_persistent_id(id_source);
 
+        let selected_before = self.selected.clone();
+
+        // Wires are compared before and after the frame to report created and removed ones,
+        // but only if anything could change them.
+        let mut wires_before = None;
+
+        let (undo, redo) = self.history_shortcuts(ui);
+        if undo || redo {
+            wires_before = Some(self.wires.iter().collect::<Vec<_>>());
+        }
+        if undo {
+            self.undo();
+        }
+        if redo {
+            self.redo();
+        }
+
+        // All edits made during this frame are undone as single step.
+        self.begin_history_group();
+
+        // Mutations requested by the viewer are applied after the graph is rendered.
+        let mut effects = Effects::new();
+
         // Draw background pattern.
         let bg_fill = style
             .bg_fill
@@ -192,7 +316,7 @@ impl<T> Snarl<T> {
             secondary_pressed: i.pointer.secondary_pressed(),
         });
 
-        Frame::none()
+        let response = Frame::none()
             .fill(bg_fill)
             .stroke(bg_stroke)
             .show(ui, |ui| {
@@ -205,9 +329,29 @@ impl<T> Snarl<T> {
                 let mut snarl_state =
                     SnarlState::load(ui.ctx(), snarl_id, pivot, viewport, self, style);
 
+                for request in SnarlViewport::take_requests(ui.ctx(), snarl_id) {
+                    self.apply_view_request(request, &mut snarl_state, ui.ctx(), viewport, style);
+                }
+
                 let mut node_style: Style = (**ui.style()).clone();
                 node_style.zoom(snarl_state.scale());
 
+                // Rectangle selection is started by dragging background with Shift or Ctrl.
+                if bg_r.drag_started_by(PointerButton::Primary)
+                    && (input.modifiers.shift || input.modifiers.command)
+                {
+                    if let Some(pos) = ui.input(|i| i.pointer.press_origin()) {
+                        snarl_state
+                            .start_rect_selection(snarl_state.screen_pos_to_graph(pos, viewport));
+                    }
+                }
+                if snarl_state.has_rect_selection() && bg_r.dragged_by(PointerButton::Primary) {
+                    if let Some(pos) = input.hover_pos {
+                        snarl_state
+                            .update_rect_selection(snarl_state.screen_pos_to_graph(pos, viewport));
+                    }
+                }
+
                 //Draw background
                 Self::draw_background(style, &snarl_state, &viewport, ui);
 
@@ -235,21 +379,19 @@ impl<T> Snarl<T> {
                     .zoomed(snarl_state.scale())
                     .unwrap_or_else(|| node_frame.shadow(Shadow::NONE).fill(Color32::TRANSPARENT));
 
-                let wire_shape_idx = match style.wire_layer {
-                    WireLayer::BehindNodes => Some(ui.painter().add(Shape::Noop)),
-                    WireLayer::AboveNodes => None,
-                };
+                // Wires behind nodes are put here after nodes are drawn.
+                let wire_shape_idx = ui.painter().add(Shape::Noop);
 
                 // Zooming
                 match input.hover_pos {
-                    Some(hover_pos) if viewport.contains(hover_pos) => {
-                        if input.scroll_delta != 0.0 {
-                            let new_scale = (snarl_state.scale()
-                                * (1.0 + input.scroll_delta * style.scale_velocity))
-                                .clamp(style.min_scale, style.max_scale);
+                    Some(hover_pos)
+                        if viewport.contains(hover_pos) && input.scroll_delta != 0.0 =>
+                    {
+                        let new_scale = (snarl_state.scale()
+                            * (1.0 + input.scroll_delta * style.scale_velocity))
+                            .clamp(style.min_scale, style.max_scale);
 
-                            snarl_state.set_scale(new_scale);
-                        }
+                        snarl_state.set_scale(new_scale);
                     }
                     _ => {}
                 }
@@ -258,22 +400,35 @@ impl<T> Snarl<T> {
                 let mut output_info = HashMap::new();
 
                 let mut pin_hovered = None;
+                let mut nodes_in_rect_selection = Vec::new();
+
+                // Reroute points can be dragged and removed with right click.
+                // They interact before nodes, so nodes above them take the pointer.
+                let mut reroutes_hovered = Vec::new();
+                for (&wire, points) in &self.wires.reroutes {
+                    for (idx, &point) in points.iter().enumerate() {
+                        let point = snarl_state.graph_pos_to_screen(point, viewport);
+                        let r = ui.interact(
+                            Rect::from_center_size(point, vec2(pin_size, pin_size)),
+                            snarl_id.with(("snarl-reroute", wire, idx)),
+                            Sense::click_and_drag(),
+                        );
+                        if r.dragged_by(PointerButton::Primary) {
+                            reroute_moved =
+                                Some((wire, idx, snarl_state.screen_vec_to_graph(r.drag_delta())));
+                        }
+                        if r.clicked_by(PointerButton::Secondary) {
+                            reroute_removed = Some((wire, idx));
+                        }
+                        if r.hovered() || r.dragged() {
+                            reroutes_hovered.push((wire, idx));
+                        }
+                    }
+                }
 
                 let draw_order = self.draw_order.clone();
-                let draw_order_len = draw_order.len();
                 let mut drag_released = false;
 
-                let mut center = vec2(0.0, 0.0);
-                let mut need_centering = false;
-                // Centering nodes
-                if style.centering{
-                    ui.input(|i|{
-                        if i.pointer.button_double_clicked(PointerButton::Primary){
-                            need_centering = true;
-                        }
-                    });
-                }
-
                 for node_idx in draw_order {
                     // show_node(node_idx);
                     let response = self.draw_node(
@@ -289,6 +444,7 @@ impl<T> Snarl<T> {
                         &mut input_info,
                         &input,
                         &mut output_info,
+                        &mut effects,
                     );
                     if let Some(v) = response.node_to_top {
                         node_to_top = Some(v);
@@ -296,21 +452,111 @@ impl<T> Snarl<T> {
                     if let Some(v) = response.node_moved {
                         node_moved = Some(v);
                     }
+                    if let Some(v) = response.node_clicked {
+                        node_clicked = Some(v);
+                    }
+                    if let Some(v) = response.node_double_clicked {
+                        node_double_clicked = Some(v);
+                    }
+                    if response.node_hovered {
+                        hovered_node = Some(node_idx);
+                    }
+                    if let Some(layout) = response.layout {
+                        node_layouts.push((node_idx, layout));
+                    }
+                    if let Some(v) = response.node_drag_started {
+                        node_drag_started = Some(v);
+                    }
+                    if let Some(v) = response.node_drag_released {
+                        node_drag_released = Some(v);
+                    }
+                    if response.in_rect_selection {
+                        nodes_in_rect_selection.push(node_idx);
+                    }
                     if let Some(v) = response.pin_hovered {
                         pin_hovered = Some(v);
                     }
                     drag_released |= response.drag_released;
-                    center += response.center;
                 }
 
+                // Wires of nodes inserted last frame are connected now that their pins are shown.
+                for (mut wires, pin) in take_pending_wires(ui.ctx(), snarl_id) {
+                    self.retain_existing_wires(viewer, &mut wires, pin, |_| {});
+                    pending_drops.push(self.plan_wires_drop(
+                        viewer,
+                        &wires,
+                        pin,
+                        &input_info,
+                        &output_info,
+                    ));
+                }
+
+                let wires_drop = match (snarl_state.new_wires(), pin_hovered) {
+                    (Some(new_wires), Some(pin)) => Some(self.plan_wires_drop(
+                        viewer,
+                        new_wires,
+                        pin,
+                        &input_info,
+                        &output_info,
+                    )),
+                    _ => None,
+                };
+
+                // Single node dragged over a wire may be spliced into it.
+                let splice_node = node_moved
+                    .map(|(node, _)| node)
+                    .or(node_drag_released)
+                    .filter(|&node| !self.is_node_selected(node) || self.selected.len() == 1);
+                let mut splice = None;
+
                 let mut hovered_wire = None;
-                let mut hovered_wire_disconnect = false;
-                let mut wire_shapes = Vec::new();
+                let mut wire_clicks = [false; egui::NUM_POINTER_BUTTONS];
+                let mut wire_menu_opened = false;
+                let mut wire_shapes_behind = Vec::new();
+                let mut wire_shapes_above = Vec::new();
                 let mut wire_hit = false;
+                let mut wire_flow_visible = false;
+                let time = ui.input(|i| i.time);
 
                 for wire in self.wires.iter() {
-                    let (from, color_from) = output_info[&wire.out_pin];
-                    let (to, color_to) = input_info[&wire.in_pin];
+                    // Wires to pins that nodes no longer have are not drawn.
+                    let (Some(&(from, color_from, _)), Some(&(to, color_to, _))) =
+                        (output_info.get(&wire.out_pin), input_info.get(&wire.in_pin))
+                    else {
+                        continue;
+                    };
+
+                    let out_pin = OutPin::new(self, wire.out_pin);
+                    let in_pin = InPin::new(self, wire.in_pin);
+                    let wire_style = viewer
+                        .wire_style(&out_pin, &in_pin, self)
+                        .unwrap_or(style.wire_style);
+                    let wire_info = viewer.wire_info(&out_pin, &in_pin, self);
+
+                    // Wire goes from output pin through reroute points to input pin.
+                    let route = std::iter::once(from)
+                        .chain(
+                            self.wires
+                                .reroutes(&wire)
+                                .iter()
+                                .map(|&point| snarl_state.graph_pos_to_screen(point, viewport)),
+                        )
+                        .chain(std::iter::once(to))
+                        .collect::<Vec<_>>();
+                    let reroutes = &route[1..route.len() - 1];
+
+                    let mut paint = WirePaint {
+                        width: wire_info
+                            .width
+                            .map_or(wire_width, |width| width * snarl_state.scale()),
+                        color: wire_info
+                            .color
+                            .unwrap_or(WireColor::Solid(mix_colors(color_from, color_to))),
+                        dash: wire_info.dash.map(|(dash, gap)| {
+                            (dash * snarl_state.scale(), gap * snarl_state.scale())
+                        }),
+                        flow: None,
+                    };
 
                     if !wire_hit
                         && !snarl_state.has_new_wires()
@@ -322,22 +568,26 @@ impl<T> Snarl<T> {
                         // And not hovering over item above.
 
                         if let Some(hover_pos) = input.hover_pos {
-                            wire_hit = hit_wire(
+                            let segment = hit_wire(
                                 hover_pos,
+                                wire_style,
                                 wire_frame_size,
                                 style.upscale_wire_frame,
                                 style.downscale_wire_frame,
-                                from,
-                                to,
-                                wire_width.max(1.5),
+                                &route,
+                                paint.width.max(1.5),
                             );
 
-                            if wire_hit {
+                            if let Some(segment) = segment {
+                                wire_hit = true;
                                 hovered_wire = Some(wire);
+                                wire_clicks = bg_r.clicked;
 
-                                //Remove hovered wire by second click
-                                hovered_wire_disconnect |=
-                                    bg_r.clicked_by(PointerButton::Secondary);
+                                // Double-click on the wire adds reroute point.
+                                if bg_r.double_clicked_by(PointerButton::Primary) {
+                                    let pos = snarl_state.screen_pos_to_graph(hover_pos, viewport);
+                                    reroute_inserted = Some((wire, segment, pos));
+                                }
 
                                 // Background is not hovered then.
                                 bg_r.hovered = false;
@@ -348,139 +598,515 @@ impl<T> Snarl<T> {
                         }
                     }
 
-                    let color = mix_colors(color_from, color_to);
+                    if let (None, Some(node), Some(hover_pos)) =
+                        (splice, splice_node, input.hover_pos)
+                    {
+                        if wire.out_pin.node != node
+                            && wire.in_pin.node != node
+                            && hit_wire(
+                                hover_pos,
+                                wire_style,
+                                wire_frame_size,
+                                style.upscale_wire_frame,
+                                style.downscale_wire_frame,
+                                &route,
+                                paint.width.max(1.5) * 2.0,
+                            )
+                            .is_some()
+                        {
+                            splice = viewer
+                                .splice_pins(node, &out_pin, &in_pin, self)
+                                .filter(|&(input, output)| {
+                                    self.can_splice(
+                                        viewer,
+                                        wire,
+                                        node,
+                                        input,
+                                        output,
+                                        &input_info,
+                                        &output_info,
+                                    )
+                                })
+                                .map(|pins| (wire, pins));
+                        }
+                    }
+
+                    // Fade wires that are replaced if dragged wires are dropped.
+                    if wires_drop
+                        .as_ref()
+                        .is_some_and(|wires_drop| wires_drop.replace.contains(&wire))
+                    {
+                        paint = paint.faded();
+                    }
+
+                    if hovered_wire == Some(wire)
+                        || splice.is_some_and(|(splice_wire, _)| splice_wire == wire)
+                    {
+                        paint.width *= 1.5;
+                    }
 
-                    let mut draw_width = wire_width;
-                    if hovered_wire == Some(wire) {
-                        draw_width *= 1.5;
+                    if let Some(flow) = wire_info.flow.filter(|_| style.animate_wires) {
+                        // Phase is computed in f64, so pulses don't jitter after long run.
+                        #[allow(clippy::cast_possible_truncation)]
+                        let phase = (time * f64::from(flow.speed))
+                            .rem_euclid(f64::from(flow.spacing))
+                            as f32;
+                        paint.flow = Some(FlowPaint {
+                            offset: phase * snarl_state.scale(),
+                            spacing: flow.spacing * snarl_state.scale(),
+                            radius: paint.width * 1.5,
+                            color: flow.color,
+                        });
                     }
 
-                    draw_wire(
+                    let wire_shapes = match wire_info.layer.unwrap_or(style.wire_layer) {
+                        WireLayer::BehindNodes => &mut wire_shapes_behind,
+                        WireLayer::AboveNodes => &mut wire_shapes_above,
+                    };
+
+                    let visible = draw_wire(
                         ui,
-                        &mut wire_shapes,
+                        wire_shapes,
+                        wire_style,
                         wire_frame_size,
                         style.upscale_wire_frame,
                         style.downscale_wire_frame,
-                        from,
-                        to,
-                        Stroke::new(draw_width, color),
+                        &route,
+                        paint,
                     );
+                    wire_flow_visible |= visible && paint.flow.is_some();
+
+                    for (idx, &point) in reroutes.iter().enumerate() {
+                        let mut radius = pin_size * 0.3;
+                        if reroutes_hovered.contains(&(wire, idx)) {
+                            radius *= 1.5;
+                        }
+                        #[allow(clippy::cast_precision_loss)]
+                        let t = (idx + 1) as f32 / (reroutes.len() + 1) as f32;
+                        wire_shapes.push(Shape::circle_filled(point, radius, paint.color.at(t)));
+                    }
+                }
+
+                // Flow is animated only while flowing wires are visible.
+                if wire_flow_visible {
+                    ui.ctx().request_repaint();
                 }
 
-                //Remove hovered wire by second click
-                if hovered_wire_disconnect {
-                    if let Some(wire) = hovered_wire {
-                        let out_pin = OutPin::new(self, wire.out_pin);
-                        let in_pin = InPin::new(self, wire.in_pin);
-                        viewer.disconnect(&out_pin, &in_pin, self);
+                let new_wire_shapes = match style.wire_layer {
+                    WireLayer::BehindNodes => &mut wire_shapes_behind,
+                    WireLayer::AboveNodes => &mut wire_shapes_above,
+                };
+
+                if let Some(wire) = hovered_wire {
+                    let out_pin = OutPin::new(self, wire.out_pin);
+                    let in_pin = InPin::new(self, wire.in_pin);
+
+                    if wire_clicks[PointerButton::Secondary as usize]
+                        && viewer.has_wire_menu(&out_pin, &in_pin, self)
+                    {
+                        // Menu is opened below together with graph menu.
+                        snarl_state.set_wire_menu(Some((wire.out_pin, wire.in_pin)));
+                        wire_menu_opened = true;
+                    } else {
+                        for button in [
+                            PointerButton::Primary,
+                            PointerButton::Secondary,
+                            PointerButton::Middle,
+                        ] {
+                            if wire_clicks[button as usize] {
+                                viewer.wire_clicked(&out_pin, &in_pin, button, self, &mut effects);
+                            }
+                        }
+                    }
+
+                    if viewer.has_wire_hover_ui(&out_pin, &in_pin, self) {
+                        egui::show_tooltip_at_pointer(
+                            ui.ctx(),
+                            snarl_id.with("snarl-wire-hover"),
+                            |ui| {
+                                viewer.wire_hover_ui(
+                                    &out_pin,
+                                    &in_pin,
+                                    ui,
+                                    snarl_state.scale(),
+                                    self,
+                                    &mut effects,
+                                );
+                            },
+                        );
                     }
                 }
 
-                if bg_r.dragged_by(PointerButton::Primary) {
+                if let Some(rect) = snarl_state.rect_selection(viewport) {
+                    let select_rect_stroke = style
+                        .select_rect_stroke
+                        .unwrap_or(ui.visuals().selection.stroke);
+                    let select_rect_fill = style
+                        .select_rect_fill
+                        .unwrap_or_else(|| ui.visuals().selection.bg_fill.gamma_multiply(0.3));
+
+                    ui.painter()
+                        .rect(rect, 0.0, select_rect_fill, select_rect_stroke);
+
+                    if !bg_r.dragged_by(PointerButton::Primary) {
+                        // Selection is finished when drag is released.
+                        snarl_state.stop_rect_selection();
+                        rect_selected = Some(nodes_in_rect_selection);
+                    }
+                } else if bg_r.dragged_by(PointerButton::Primary) {
                     snarl_state.pan(-bg_r.drag_delta());
                 }
 
-                //Do centering
-                if need_centering && bg_r.double_clicked() {
-                    center /= draw_order_len as f32;
-                    snarl_state.set_offset(center * snarl_state.scale());
-                }
+                bg_clicked = bg_r.clicked_by(PointerButton::Primary);
 
-                bg_r.context_menu(|ui| {
-                    viewer.graph_menu(
-                        snarl_state.screen_pos_to_graph(ui.cursor().min, viewport),
-                        ui,
-                        snarl_state.scale(),
-                        self,
+                if style.centering && bg_r.double_clicked_by(PointerButton::Primary) {
+                    self.apply_view_request(
+                        ViewRequest::Fit(None),
+                        &mut snarl_state,
+                        ui.ctx(),
+                        viewport,
+                        style,
                     );
+                }
+
+                if ui.rect_contains_pointer(viewport) {
+                    pointer_pos = input
+                        .hover_pos
+                        .map(|pos| snarl_state.screen_pos_to_graph(pos, viewport));
+                    self.handle_clipboard_events(ui, viewer, pointer_pos, &mut effects);
+                }
+
+                // Right-click on the background opens graph menu instead of wire menu.
+                if bg_r.secondary_clicked() {
+                    snarl_state.set_wire_menu(None);
+                }
+
+                // Wire and graph menus share the background response,
+                // so only one of them is open at a time.
+                let mut menu_r = bg_r.clone();
+                if wire_menu_opened {
+                    menu_r.hovered = true;
+                    menu_r.clicked = wire_clicks;
+                }
+
+                menu_r.context_menu(|ui| match snarl_state.wire_menu() {
+                    Some((out_pin, in_pin)) => {
+                        if !self.is_connected(out_pin, in_pin) {
+                            ui.close_menu();
+                            return;
+                        }
+                        viewer.wire_menu(
+                            &OutPin::new(self, out_pin),
+                            &InPin::new(self, in_pin),
+                            ui,
+                            snarl_state.scale(),
+                            self,
+                            &mut effects,
+                        );
+                    }
+                    None => {
+                        viewer.graph_menu(
+                            snarl_state.screen_pos_to_graph(ui.cursor().min, viewport),
+                            ui,
+                            snarl_state.scale(),
+                            self,
+                            &mut effects,
+                        );
+                    }
                 });
 
-                
+                if let Some((pos, src_pins)) = snarl_state.dropped_wires().cloned() {
+                    let alive = match &src_pins {
+                        NewWires::In(pins) => pins.iter().all(|pin| self.contains_node(pin.node)),
+                        NewWires::Out(pins) => pins.iter().all(|pin| self.contains_node(pin.node)),
+                    };
+
+                    let queued = effects.effects.len();
+                    let area_r = alive.then(|| {
+                        egui::Area::new(snarl_id.with("snarl-dropped-wire-menu"))
+                            .order(egui::Order::Foreground)
+                            .fixed_pos(snarl_state.graph_pos_to_screen(pos, viewport))
+                            .show(ui.ctx(), |ui| {
+                                Frame::menu(ui.style()).show(ui, |ui| {
+                                    viewer.dropped_wire_menu(
+                                        pos,
+                                        &src_pins,
+                                        ui,
+                                        snarl_state.scale(),
+                                        self,
+                                        &mut effects,
+                                    );
+                                });
+                            })
+                            .response
+                    });
+
+                    // Menu is closed once viewer acted, on Escape or on click elsewhere.
+                    let close = match area_r {
+                        None => true,
+                        Some(area_r) => {
+                            effects.effects.len() != queued
+                                || ui.input(|i| {
+                                    i.key_pressed(Key::Escape)
+                                        || (i.pointer.any_pressed()
+                                            && !i
+                                                .pointer
+                                                .interact_pos()
+                                                .is_some_and(|pos| area_r.rect.contains(pos)))
+                                })
+                        }
+                    };
+                    if close {
+                        snarl_state.set_dropped_wires(None);
+                    }
+                }
 
                 match snarl_state.new_wires() {
                     None => {}
                     Some(NewWires::In(pins)) => {
                         for pin in pins {
                             let from = input.hover_pos.unwrap_or(Pos2::ZERO);
-                            let (to, color) = input_info[pin];
+                            let Some(&(to, mut color, _)) = input_info.get(pin) else {
+                                continue;
+                            };
+
+                            if let Some(AnyPin::Out(out_pin)) = pin_hovered {
+                                if !viewer.can_connect(
+                                    &OutPin::new(self, out_pin),
+                                    &InPin::new(self, *pin),
+                                    self,
+                                ) {
+                                    color = ui.visuals().error_fg_color;
+                                }
+                            }
 
                             draw_wire(
                                 ui,
-                                &mut wire_shapes,
+                                new_wire_shapes,
+                                style.wire_style,
                                 wire_frame_size,
                                 style.upscale_wire_frame,
                                 style.downscale_wire_frame,
-                                from,
-                                to,
-                                Stroke::new(wire_width, color),
+                                &[from, to],
+                                WirePaint::new(wire_width, color),
                             );
                         }
                     }
                     Some(NewWires::Out(pins)) => {
                         for pin in pins {
-                            let (from, color) = output_info[pin];
+                            let Some(&(from, mut color, _)) = output_info.get(pin) else {
+                                continue;
+                            };
                             let to = input.hover_pos.unwrap_or(Pos2::ZERO);
 
+                            if let Some(AnyPin::In(in_pin)) = pin_hovered {
+                                if !viewer.can_connect(
+                                    &OutPin::new(self, *pin),
+                                    &InPin::new(self, in_pin),
+                                    self,
+                                ) {
+                                    color = ui.visuals().error_fg_color;
+                                }
+                            }
+
                             draw_wire(
                                 ui,
-                                &mut wire_shapes,
+                                new_wire_shapes,
+                                style.wire_style,
                                 wire_frame_size,
                                 style.upscale_wire_frame,
                                 style.downscale_wire_frame,
-                                from,
-                                to,
-                                Stroke::new(wire_width, color),
+                                &[from, to],
+                                WirePaint::new(wire_width, color),
                             );
                         }
                     }
                 }
 
-                match wire_shape_idx {
-                    None => {
-                        ui.painter().add(Shape::Vec(wire_shapes));
-                    }
-                    Some(idx) => {
-                        ui.painter().set(idx, Shape::Vec(wire_shapes));
-                    }
-                }
+                ui.painter()
+                    .set(wire_shape_idx, Shape::Vec(wire_shapes_behind));
+                ui.painter().add(Shape::Vec(wire_shapes_above));
 
                 if drag_released {
                     let new_wires = snarl_state.take_wires();
                     if new_wires.is_some() {
                         ui.ctx().request_repaint();
                     }
-                    match (new_wires, pin_hovered) {
-                        (Some(NewWires::In(in_pins)), Some(AnyPin::Out(out_pin))) => {
-                            for in_pin in in_pins {
-                                viewer.connect(
-                                    &OutPin::new(self, out_pin),
-                                    &InPin::new(self, in_pin),
-                                    self,
-                                );
-                            }
+
+                    // Wires dropped on empty space may be connected to a new node.
+                    if let (None, None, Some(new_wires), Some(hover_pos)) =
+                        (&wires_drop, hovered_node, new_wires, input.hover_pos)
+                    {
+                        if viewport.contains(hover_pos)
+                            && viewer.has_dropped_wire_menu(&new_wires, self)
+                        {
+                            let pos = snarl_state.screen_pos_to_graph(hover_pos, viewport);
+                            snarl_state.set_dropped_wires(Some((pos, new_wires)));
                         }
-                        (Some(NewWires::Out(out_pins)), Some(AnyPin::In(in_pin))) => {
-                            for out_pin in out_pins {
-                                viewer.connect(
-                                    &OutPin::new(self, out_pin),
-                                    &InPin::new(self, in_pin),
-                                    self,
-                                );
-                            }
+                    }
+
+                    if let Some(wires_drop) = wires_drop {
+                        for wire in wires_drop.replace {
+                            let from = OutPin::new(self, wire.out_pin);
+                            let to = InPin::new(self, wire.in_pin);
+                            viewer.disconnect(&from, &to, self, &mut effects);
+                        }
+                        for (out_pin, in_pin) in wires_drop.connect {
+                            let from = OutPin::new(self, out_pin);
+                            let to = InPin::new(self, in_pin);
+                            viewer.connect(&from, &to, self, &mut effects);
                         }
-                        _ => {}
                     }
                 }
 
+                if let (Some(node), Some((wire, (input, output)))) = (node_drag_released, splice) {
+                    let from = OutPin::new(self, wire.out_pin);
+                    let to = InPin::new(self, wire.in_pin);
+                    let node_in = InPin::new(self, InPinId { node, input });
+                    let node_out = OutPin::new(self, OutPinId { node, output });
+                    viewer.disconnect(&from, &to, self, &mut effects);
+                    viewer.connect(&from, &node_in, self, &mut effects);
+                    viewer.connect(&node_out, &to, self, &mut effects);
+                }
+
                 ui.advance_cursor_after_rect(Rect::from_min_size(viewport.min, Vec2::ZERO));
 
                 snarl_state.store(ui.ctx());
-            });
 
+                hovered_pin = pin_hovered;
+                wire_hovered = hovered_wire;
+                bg_r
+            })
+            .inner;
+
+        for (node, layout) in node_layouts {
+            if let Some(entry) = self.nodes.get_mut(node.index) {
+                entry.layout = Some(layout);
+            }
+        }
+
+        // Shift or Ctrl extends selection.
+        let extend_selection = input.modifiers.shift || input.modifiers.command;
+
+        if let Some(node) = node_clicked {
+            if !extend_selection {
+                self.set_selection([node]);
+            } else if self.is_node_selected(node) {
+                self.deselect_node(node);
+            } else {
+                self.select_node(node);
+            }
+        }
+
+        if let Some(node) = node_drag_started {
+            if extend_selection {
+                self.select_node(node);
+            } else if !self.is_node_selected(node) {
+                self.set_selection([node]);
+            }
+        }
+
+        if let Some(nodes) = rect_selected {
+            ui.ctx().request_repaint();
+            for node in nodes {
+                self.select_node(node);
+            }
+        }
+
+        if bg_clicked && !extend_selection {
+            self.clear_selection();
+        }
+
+        let mut reroutes_changed = false;
+        if let Some((wire, idx, delta)) = reroute_moved {
+            ui.ctx().request_repaint();
+            if let Some(&pos) = self.wires.reroutes(&wire).get(idx) {
+                reroutes_changed |= self.move_reroute(wire.out_pin, wire.in_pin, idx, pos + delta);
+            }
+        }
+        if let Some((wire, idx)) = reroute_removed {
+            ui.ctx().request_repaint();
+            reroutes_changed |= self
+                .remove_reroute(wire.out_pin, wire.in_pin, idx)
+                .is_some();
+        }
+        if let Some((wire, idx, pos)) = reroute_inserted {
+            ui.ctx().request_repaint();
+            reroutes_changed |= self.insert_reroute(wire.out_pin, wire.in_pin, idx, pos);
+        }
+
+        let mut moved_nodes = Vec::new();
         if let Some((node, delta)) = node_moved {
             ui.ctx().request_repaint();
-            let node = &mut self.nodes[node.0];
-            node.pos += delta;
+            if self.is_node_selected(node) {
+                // Dragging selected node moves whole selection.
+                moved_nodes = self.selected.clone();
+            } else {
+                moved_nodes.push(node);
+            }
+            for &node in &moved_nodes {
+                self.move_node(node, delta);
+            }
+        }
+
+        let mut pending_connected = false;
+        if !pending_drops.is_empty() {
+            ui.ctx().request_repaint();
+            if wires_before.is_none() {
+                wires_before = Some(self.wires.iter().collect());
+            }
+            for wires_drop in pending_drops {
+                for wire in wires_drop.replace {
+                    pending_connected |= self.disconnect(wire.out_pin, wire.in_pin);
+                }
+                for (out_pin, in_pin) in wires_drop.connect {
+                    pending_connected |= self.connect(out_pin, in_pin);
+                }
+            }
+        }
+
+        let effects_applied = !effects.is_empty();
+        let mut effect_errors = Vec::new();
+        let mut wires_pending = false;
+        if !effects.is_empty() {
+            ui.ctx().request_repaint();
+            if wires_before.is_none() {
+                wires_before = Some(self.wires.iter().collect());
+            }
+            for effect in effects.effects {
+                if let Effect::InsertConnectedNode {
+                    pos,
+                    node,
+                    mut wires,
+                    pin,
+                } = effect
+                {
+                    let node = self.insert_node(pos, node);
+                    let pin = match wires {
+                        NewWires::Out(_) => AnyPin::In(InPinId { node, input: pin }),
+                        NewWires::In(_) => AnyPin::Out(OutPinId { node, output: pin }),
+                    };
+                    // Wires to pins the nodes do not have are dropped.
+                    self.retain_existing_wires(viewer, &mut wires, pin, |err| {
+                        effect_errors.push(err);
+                    });
+                    // Limits of the new pin are known when the node is shown.
+                    push_pending_wires(ui.ctx(), snarl_id, wires, pin);
+                    wires_pending = true;
+                    continue;
+                }
+                if let Effect::Connect { from, to } = effect {
+                    // Wires to pins the nodes do not have are dropped.
+                    if let Err(err) = self.check_wire(viewer, from, to) {
+                        effect_errors.push(err);
+                        continue;
+                    }
+                }
+                // Effects of stale nodes are skipped and reported.
+                if let Err(err) = self.try_apply_effect(effect) {
+                    effect_errors.push(err);
+                }
+            }
         }
 
         if let Some(node_idx) = node_to_top {
@@ -490,6 +1116,372 @@ impl<T> Snarl<T> {
                 self.draw_order.push(node_idx);
             }
         }
+
+        // Keep dragging gesture in single step until the node is released.
+        // Inserted node and its wires connected next frame are undone together too.
+        if let Some(history) = &mut self.history {
+            history.set_hold(node_moved.is_some() || reroute_moved.is_some() || wires_pending);
+        }
+        self.end_history_group();
+
+        let mut wires_created = Vec::new();
+        let mut wires_removed = Vec::new();
+        if let Some(before) = wires_before {
+            let after = self.wires.iter().collect::<Vec<_>>();
+            let before_set = before.iter().copied().collect::<HashSet<_>>();
+            let after_set = after.iter().copied().collect::<HashSet<_>>();
+
+            wires_created.extend(
+                after
+                    .into_iter()
+                    .filter(|wire| !before_set.contains(wire))
+                    .map(|wire| (wire.out_pin, wire.in_pin)),
+            );
+            wires_removed.extend(
+                before
+                    .into_iter()
+                    .filter(|wire| !after_set.contains(wire))
+                    .map(|wire| (wire.out_pin, wire.in_pin)),
+            );
+        }
+
+        SnarlResponse {
+            response,
+            id: snarl_id,
+            hovered_node,
+            hovered_pin,
+            hovered_wire: wire_hovered.map(|wire| (wire.out_pin, wire.in_pin)),
+            changed: undo
+                || redo
+                || effects_applied
+                || pending_connected
+                || reroutes_changed
+                || !moved_nodes.is_empty(),
+            moved_nodes,
+            clicked_node: node_clicked,
+            double_clicked_node: node_double_clicked,
+            wires_created,
+            wires_removed,
+            effect_errors,
+            selection_changed: self.selected != selected_before,
+            pointer_pos,
+        }
+    }
+
+    /// Checks if undo or redo shortcut is pressed
+    /// while pointer is over the graph and no widget has keyboard focus.
+    /// Returns `(undo, redo)`.
+    fn history_shortcuts(&self, ui: &mut Ui) -> (bool, bool) {
+        if self.history.is_none()
+            || !ui.rect_contains_pointer(ui.max_rect())
+            || ui.memory(|m| m.focus().is_some())
+        {
+            return (false, false);
+        }
+
+        ui.input_mut(|i| {
+            // Check more specific shortcut first.
+            let redo = i.consume_shortcut(&KeyboardShortcut::new(
+                Modifiers::COMMAND | Modifiers::SHIFT,
+                Key::Z,
+            )) || i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Y));
+            let undo = i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z));
+            (undo, redo)
+        })
+    }
+
+    /// Checks that both nodes exist and have the pins
+    /// according to the viewer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError::UnknownNode`] if either node does not exist,
+    /// [`SnarlError::UnknownOutPin`] or [`SnarlError::UnknownInPin`]
+    /// if the node has fewer pins than the wire refers to.
+    pub(crate) fn check_wire<V>(
+        &self,
+        viewer: &mut V,
+        from: OutPinId,
+        to: InPinId,
+    ) -> Result<(), SnarlError>
+    where
+        V: SnarlViewer<T>,
+    {
+        let out_node = self
+            .get_node(from.node)
+            .ok_or(SnarlError::UnknownNode(from.node))?;
+        let in_node = self
+            .get_node(to.node)
+            .ok_or(SnarlError::UnknownNode(to.node))?;
+
+        if from.output >= viewer.outputs(out_node) {
+            return Err(SnarlError::UnknownOutPin(from));
+        }
+        if to.input >= viewer.inputs(in_node) {
+            return Err(SnarlError::UnknownInPin(to));
+        }
+        Ok(())
+    }
+
+    /// Applies change of the viewport requested through [`SnarlViewport`].
+    fn apply_view_request(
+        &self,
+        request: ViewRequest,
+        snarl_state: &mut SnarlState,
+        cx: &Context,
+        viewport: Rect,
+        style: &SnarlStyle,
+    ) {
+        match request {
+            ViewRequest::Offset(offset) => snarl_state.set_offset(offset),
+            ViewRequest::Scale(scale) => {
+                snarl_state.set_scale_now(cx, scale.clamp(style.min_scale, style.max_scale));
+            }
+            ViewRequest::Fit(nodes) => {
+                let nodes = match nodes {
+                    None => self.draw_order.clone(),
+                    Some(nodes) => nodes,
+                };
+                let mut bb = Rect::NOTHING;
+                for node in nodes {
+                    if let Some(rect) = self.node_view_rect(node) {
+                        bb = bb.union(rect);
+                    }
+                }
+                if bb.is_positive() {
+                    let bb = bb.expand(FIT_MARGIN);
+                    snarl_state.animate_to(cx, bb.center(), fit_scale(bb, viewport, style));
+                }
+            }
+            ViewRequest::Center(node) => {
+                if let Some(rect) = self.node_view_rect(node) {
+                    snarl_state.animate_to(cx, rect.center(), snarl_state.scale());
+                }
+            }
+        }
+    }
+
+    /// Returns node rect for viewport requests.
+    /// Nodes that were not laid out yet are treated as points.
+    fn node_view_rect(&self, node: NodeId) -> Option<Rect> {
+        if !self.contains_node(node) {
+            return None;
+        }
+        self.node_rect(node)
+            .or_else(|| Some(Rect::from_min_size(self.node(node).pos, Vec2::ZERO)))
+    }
+
+    /// Plans connection of dragged wires to the pin they are dropped on.
+    ///
+    /// Incompatible pins are skipped.
+    /// Oldest wires of pins that would exceed their connection limits are replaced.
+    fn plan_wires_drop<V>(
+        &self,
+        viewer: &mut V,
+        new_wires: &NewWires,
+        pin: AnyPin,
+        input_info: &HashMap<InPinId, (Pos2, Color32, Option<usize>)>,
+        output_info: &HashMap<OutPinId, (Pos2, Color32, Option<usize>)>,
+    ) -> WiresDrop
+    where
+        V: SnarlViewer<T>,
+    {
+        let in_limit = |pin: InPinId| input_info.get(&pin).and_then(|info| info.2);
+        let out_limit = |pin: OutPinId| output_info.get(&pin).and_then(|info| info.2);
+
+        let mut connect = match (new_wires, pin) {
+            (NewWires::Out(out_pins), AnyPin::In(in_pin)) => {
+                out_pins.iter().map(|&out_pin| (out_pin, in_pin)).collect()
+            }
+            (NewWires::In(in_pins), AnyPin::Out(out_pin)) => {
+                in_pins.iter().map(|&in_pin| (out_pin, in_pin)).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        // Wires from a node to itself are rejected by `Snarl::try_connect`.
+        connect.retain(|&(out_pin, in_pin)| {
+            out_pin.node != in_pin.node
+                && !self.is_connected(out_pin, in_pin)
+                && in_limit(in_pin) != Some(0)
+                && out_limit(out_pin) != Some(0)
+                && viewer.can_connect(&OutPin::new(self, out_pin), &InPin::new(self, in_pin), self)
+        });
+
+        // Pin under the cursor receives all new wires.
+        let limit = match pin {
+            AnyPin::In(in_pin) => in_limit(in_pin),
+            AnyPin::Out(out_pin) => out_limit(out_pin),
+        };
+        if let Some(limit) = limit {
+            connect.truncate(limit);
+        }
+
+        let mut replace = Vec::new();
+
+        for &(out_pin, in_pin) in &connect {
+            if let Some(limit) = in_limit(in_pin) {
+                let new = connect.iter().filter(|&&(_, pin)| pin == in_pin).count();
+                let existing = self.wires.wired_outputs(in_pin).collect::<Vec<_>>();
+                let excess = (existing.len() + new).saturating_sub(limit);
+                for out_pin in existing.into_iter().take(excess) {
+                    let wire = Wire { out_pin, in_pin };
+                    if !replace.contains(&wire) {
+                        replace.push(wire);
+                    }
+                }
+            }
+
+            if let Some(limit) = out_limit(out_pin) {
+                let new = connect.iter().filter(|&&(pin, _)| pin == out_pin).count();
+                let existing = self.wires.wired_inputs(out_pin).collect::<Vec<_>>();
+                let excess = (existing.len() + new).saturating_sub(limit);
+                for in_pin in existing.into_iter().take(excess) {
+                    let wire = Wire { out_pin, in_pin };
+                    if !replace.contains(&wire) {
+                        replace.push(wire);
+                    }
+                }
+            }
+        }
+
+        WiresDrop { connect, replace }
+    }
+
+    /// Checks that `node` can be spliced into `wire` with given pins.
+    ///
+    /// Both new wires must be compatible and fit into connection limits of the node's pins.
+    /// Pins of the wire keep the number of connections, since their wire is replaced.
+    #[allow(clippy::too_many_arguments)]
+    fn can_splice<V>(
+        &self,
+        viewer: &mut V,
+        wire: Wire,
+        node: NodeId,
+        input: usize,
+        output: usize,
+        input_info: &HashMap<InPinId, (Pos2, Color32, Option<usize>)>,
+        output_info: &HashMap<OutPinId, (Pos2, Color32, Option<usize>)>,
+    ) -> bool
+    where
+        V: SnarlViewer<T>,
+    {
+        let node_in = InPinId { node, input };
+        let node_out = OutPinId { node, output };
+
+        if self.check_wire(viewer, wire.out_pin, node_in).is_err()
+            || self.check_wire(viewer, node_out, wire.in_pin).is_err()
+            || self.is_connected(wire.out_pin, node_in)
+            || self.is_connected(node_out, wire.in_pin)
+        {
+            return false;
+        }
+
+        let in_limit = input_info.get(&node_in).and_then(|info| info.2);
+        let out_limit = output_info.get(&node_out).and_then(|info| info.2);
+        if in_limit.is_some_and(|limit| self.wires.wired_outputs(node_in).count() >= limit)
+            || out_limit.is_some_and(|limit| self.wires.wired_inputs(node_out).count() >= limit)
+        {
+            return false;
+        }
+
+        viewer.can_connect(
+            &OutPin::new(self, wire.out_pin),
+            &InPin::new(self, node_in),
+            self,
+        ) && viewer.can_connect(
+            &OutPin::new(self, node_out),
+            &InPin::new(self, wire.in_pin),
+            self,
+        )
+    }
+
+    /// Keeps only dragged wires that can exist between their pins and the `pin`.
+    ///
+    /// Errors of dropped wires are passed to `on_error`.
+    fn retain_existing_wires<V>(
+        &self,
+        viewer: &mut V,
+        wires: &mut NewWires,
+        pin: AnyPin,
+        mut on_error: impl FnMut(SnarlError),
+    ) where
+        V: SnarlViewer<T>,
+    {
+        let mut check = |from: OutPinId, to: InPinId| {
+            self.check_wire(viewer, from, to)
+                .map_err(&mut on_error)
+                .is_ok()
+        };
+
+        match (wires, pin) {
+            (NewWires::Out(out_pins), AnyPin::In(in_pin)) => {
+                out_pins.retain(|&out_pin| check(out_pin, in_pin));
+            }
+            (NewWires::In(in_pins), AnyPin::Out(out_pin)) => {
+                in_pins.retain(|&in_pin| check(out_pin, in_pin));
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles copy, cut, paste and duplicate of selected nodes
+    /// when no widget has keyboard focus.
+    fn handle_clipboard_events<V>(
+        &self,
+        ui: &mut Ui,
+        viewer: &mut V,
+        pointer_pos: Option<Pos2>,
+        effects: &mut Effects<T>,
+    ) where
+        V: SnarlViewer<T>,
+    {
+        if ui.memory(|m| m.focus().is_some()) {
+            return;
+        }
+
+        let (copy, cut, paste) = ui.input(|i| {
+            let mut copy = false;
+            let mut cut = false;
+            let mut paste = None;
+            for event in &i.events {
+                match event {
+                    Event::Copy => copy = true,
+                    Event::Cut => cut = true,
+                    Event::Paste(text) => paste = Some(text.clone()),
+                    _ => {}
+                }
+            }
+            (copy, cut, paste)
+        });
+
+        let duplicate = !self.selected.is_empty()
+            && ui.input_mut(|i| {
+                i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::D))
+            });
+
+        if (copy || cut || duplicate) && !self.selected.is_empty() {
+            if let Some(clip) = viewer.copy_nodes(&self.selected, self) {
+                if duplicate {
+                    if let Some(min) = clip.min_pos() {
+                        effects.paste(clip, min + DUPLICATE_OFFSET);
+                    }
+                } else if let Some(text) = viewer.clipboard_text(&clip) {
+                    ui.output_mut(|o| o.copied_text = text);
+                    if cut {
+                        for &node in &self.selected {
+                            effects.remove_node(node);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let (Some(text), Some(pos)) = (paste, pointer_pos) {
+            if let Some(clip) = viewer.parse_clipboard_text(&text) {
+                effects.paste(clip, pos);
+            }
+        }
     }
 
     //First step for split big function to parts
@@ -498,7 +1490,7 @@ impl<T> Snarl<T> {
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::too_many_arguments)]
     fn draw_node<V>(
-        &mut self,
+        &self,
         ui: &mut Ui,
         node: NodeId,
         viewer: &mut V,
@@ -508,9 +1500,10 @@ impl<T> Snarl<T> {
         node_style: &Style,
         node_frame: &Frame,
         header_frame: &Frame,
-        input_positions: &mut HashMap<InPinId, (Pos2, Color32)>,
+        input_positions: &mut HashMap<InPinId, (Pos2, Color32, Option<usize>)>,
         input: &Input,
-        output_positions: &mut HashMap<OutPinId, (Pos2, Color32)>,
+        output_positions: &mut HashMap<OutPinId, (Pos2, Color32, Option<usize>)>,
+        effects: &mut Effects<T>,
     ) -> DrawNodeResponse
     where
         V: SnarlViewer<T>,
@@ -519,14 +1512,22 @@ impl<T> Snarl<T> {
             pos,
             open,
             ref value,
-        } = self.nodes[node.0];
+            layout,
+            ..
+        } = *self.node(node);
 
         let mut response = DrawNodeResponse {
             node_to_top: None,
             node_moved: None,
+            node_clicked: None,
+            node_double_clicked: None,
+            node_drag_started: None,
+            node_drag_released: None,
+            node_hovered: false,
+            in_rect_selection: false,
+            layout: None,
             drag_released: false,
             pin_hovered: None,
-            center: pos.to_vec2()
         };
 
         let viewport = ui.max_rect();
@@ -542,14 +1543,29 @@ impl<T> Snarl<T> {
 
         let openness = ui.ctx().animate_bool(node_id, open);
 
-        let mut node_state =
-            NodeState::load(ui.ctx(), node_id, &node_style.spacing, snarl_state.scale());
+        let mut node_state = NodeState::load(layout, &node_style.spacing, snarl_state.scale());
 
         let node_rect = node_state.node_rect(node_pos, openness);
+        let payload_right = node_rect.left() + node_state.open_width();
 
         // Rect for node + frame margin.
         let node_frame_rect = node_frame.total_margin().expand_rect(node_rect);
 
+        response.in_rect_selection = snarl_state
+            .rect_selection(viewport)
+            .is_some_and(|rect| rect.intersects(node_frame_rect));
+
+        // Highlight selected nodes and nodes that are about to be selected.
+        let node_frame = if response.in_rect_selection || self.is_node_selected(node) {
+            let select_stroke = style
+                .select_stroke
+                .zoomed(snarl_state.scale())
+                .unwrap_or(node_style.visuals.selection.stroke);
+            node_frame.stroke(select_stroke)
+        } else {
+            *node_frame
+        };
+
         let pin_size = style
             .pin_size
             .unwrap_or(node_style.spacing.interact_size.y * 0.5);
@@ -576,28 +1592,45 @@ impl<T> Snarl<T> {
         if r.clicked() || r.dragged() {
             response.node_to_top = Some(node);
         }
+        if r.clicked_by(PointerButton::Primary) {
+            response.node_clicked = Some(node);
+        }
+        if r.double_clicked_by(PointerButton::Primary) {
+            response.node_double_clicked = Some(node);
+        }
+        if r.drag_started_by(PointerButton::Primary) {
+            response.node_drag_started = Some(node);
+        }
+        if r.drag_released_by(PointerButton::Primary) {
+            response.node_drag_released = Some(node);
+        }
+        response.node_hovered = r.hovered();
         let r = r.context_menu(|ui| {
-            viewer.node_menu(node, &inputs, &outputs, ui, snarl_state.scale(), self);
+            viewer.node_menu(
+                node,
+                &inputs,
+                &outputs,
+                ui,
+                snarl_state.scale(),
+                self,
+                effects,
+            );
         });
 
-        if !self.nodes.contains(node.0) {
-            node_state.clear(ui.ctx());
-            // If removed
-            return response;
-        }
-
-        if viewer.has_on_hover_popup(&self.nodes[node.0].value) {
+        if viewer.has_on_hover_popup(value) {
             r.on_hover_ui_at_pointer(|ui| {
-                viewer.show_on_hover_popup(node, &inputs, &outputs, ui, snarl_state.scale(), self);
+                viewer.show_on_hover_popup(
+                    node,
+                    &inputs,
+                    &outputs,
+                    ui,
+                    snarl_state.scale(),
+                    self,
+                    effects,
+                );
             });
         }
 
-        if !self.nodes.contains(node.0) {
-            node_state.clear(ui.ctx());
-            // If removed
-            return response;
-        }
-
         let node_ui = &mut ui.child_ui_with_id_source(
             node_frame_rect,
             Layout::top_down(Align::Center),
@@ -629,13 +1662,21 @@ impl<T> Snarl<T> {
 
                         if r.clicked_by(PointerButton::Primary) {
                             // Toggle node's openness.
-                            self.open_node(node, !open);
+                            effects.open_node(node, !open);
                         }
                     }
 
                     ui.allocate_exact_size(header_drag_space, Sense::hover());
 
-                    viewer.show_header(node, &inputs, &outputs, ui, snarl_state.scale(), self);
+                    viewer.show_header(
+                        node,
+                        &inputs,
+                        &outputs,
+                        ui,
+                        snarl_state.scale(),
+                        self,
+                        effects,
+                    );
 
                     header_rect = ui.min_rect();
                 });
@@ -654,11 +1695,7 @@ impl<T> Snarl<T> {
             ui.expand_to_include_rect(header_rect);
             let header_size = header_rect.size();
             node_state.set_header_height(header_size.y);
-
-            if !self.nodes.contains(node.0) {
-                // If removed
-                return;
-            }
+            node_state.set_header_width(header_size.x);
 
             let min_pin_y = header_rect.center().y;
 
@@ -681,7 +1718,7 @@ impl<T> Snarl<T> {
                     header_frame_rect.max.y + node_style.spacing.item_spacing.y
                         - node_state.payload_offset(openness),
                 ),
-                pos2(f32::max(node_rect.max.x, header_rect.max.x), f32::INFINITY),
+                pos2(f32::max(payload_right, header_rect.max.x), f32::INFINITY),
             );
 
             let payload_clip_rect = Rect::from_min_max(
@@ -709,11 +1746,8 @@ impl<T> Snarl<T> {
                     let y0 = ui.cursor().min.y;
 
                     // Show input content
-                    let pin_info = viewer.show_input(in_pin, ui, snarl_state.scale(), self);
-                    if !self.nodes.contains(node.0) {
-                        // If removed
-                        return;
-                    }
+                    let pin_info =
+                        viewer.show_input(in_pin, ui, snarl_state.scale(), self, effects);
 
                     let y1 = ui.min_rect().max.y;
 
@@ -724,7 +1758,19 @@ impl<T> Snarl<T> {
 
                     let pin_pos = pos2(input_x, y);
 
-                    input_positions.insert(in_pin.id, (pin_pos, pin_info.fill));
+                    input_positions.insert(
+                        in_pin.id,
+                        (pin_pos, pin_info.fill, pin_info.max_connections),
+                    );
+
+                    // Check if dragged wires can be connected to this pin.
+                    let compatible = match snarl_state.new_wires() {
+                        Some(NewWires::Out(out_pins)) => Some(out_pins.iter().all(|&out_pin| {
+                            out_pin.node != node
+                                && viewer.can_connect(&OutPin::new(self, out_pin), in_pin, self)
+                        })),
+                        _ => None,
+                    };
 
                     // Interact with pin shape.
                     let r = ui.interact(
@@ -737,22 +1783,14 @@ impl<T> Snarl<T> {
                         if snarl_state.has_new_wires() {
                             snarl_state.remove_new_wire_in(in_pin.id);
                         } else {
-                            viewer.drop_inputs(in_pin, self);
-                            if !self.nodes.contains(node.0) {
-                                // If removed
-                                return;
-                            }
+                            viewer.drop_inputs(in_pin, self, effects);
                         }
                     }
                     if r.drag_started_by(PointerButton::Primary) {
                         if input.modifiers.command {
                             snarl_state.start_new_wires_out(&in_pin.remotes);
                             if !input.modifiers.shift {
-                                self.drop_inputs(in_pin.id);
-                                if !self.nodes.contains(node.0) {
-                                    // If removed
-                                    return;
-                                }
+                                effects.drop_inputs(in_pin.id);
                             }
                         } else {
                             snarl_state.start_new_wire_in(in_pin.id);
@@ -777,6 +1815,7 @@ impl<T> Snarl<T> {
                         _ => {}
                     }
 
+                    let pin_info = mark_compatibility(pin_info, compatible, node_style);
                     draw_pin(ui.painter(), pin_info, pin_pos, pin_size);
                 });
             }
@@ -784,11 +1823,6 @@ impl<T> Snarl<T> {
             ui.expand_to_include_rect(inputs_rect.intersect(payload_clip_rect));
             let inputs_size = inputs_rect.size();
 
-            if !self.nodes.contains(node.0) {
-                // If removed
-                return;
-            }
-
             // Show output pins.
 
             // Outputs are placed under the header and must not go outside of the header frame.
@@ -812,11 +1846,8 @@ impl<T> Snarl<T> {
                     let y0 = ui.cursor().min.y;
 
                     // Show output content
-                    let pin_info = viewer.show_output(out_pin, ui, snarl_state.scale(), self);
-                    if !self.nodes.contains(node.0) {
-                        // If removed
-                        return;
-                    }
+                    let pin_info =
+                        viewer.show_output(out_pin, ui, snarl_state.scale(), self, effects);
 
                     let y1 = ui.min_rect().max.y;
 
@@ -827,7 +1858,19 @@ impl<T> Snarl<T> {
 
                     let pin_pos = pos2(output_x, y);
 
-                    output_positions.insert(out_pin.id, (pin_pos, pin_info.fill));
+                    output_positions.insert(
+                        out_pin.id,
+                        (pin_pos, pin_info.fill, pin_info.max_connections),
+                    );
+
+                    // Check if dragged wires can be connected to this pin.
+                    let compatible = match snarl_state.new_wires() {
+                        Some(NewWires::In(in_pins)) => Some(in_pins.iter().all(|&in_pin| {
+                            in_pin.node != node
+                                && viewer.can_connect(out_pin, &InPin::new(self, in_pin), self)
+                        })),
+                        _ => None,
+                    };
 
                     let r = ui.interact(
                         Rect::from_center_size(pin_pos, vec2(pin_size, pin_size)),
@@ -839,11 +1882,7 @@ impl<T> Snarl<T> {
                         if snarl_state.has_new_wires() {
                             snarl_state.remove_new_wire_out(out_pin.id);
                         } else {
-                            viewer.drop_outputs(out_pin, self);
-                            if !self.nodes.contains(node.0) {
-                                // If removed
-                                return;
-                            }
+                            viewer.drop_outputs(out_pin, self, effects);
                         }
                     }
                     if r.drag_started_by(PointerButton::Primary) {
@@ -851,11 +1890,7 @@ impl<T> Snarl<T> {
                             snarl_state.start_new_wires_in(&out_pin.remotes);
 
                             if !input.modifiers.shift {
-                                self.drop_outputs(out_pin.id);
-                                if !self.nodes.contains(node.0) {
-                                    // If removed
-                                    return;
-                                }
+                                effects.drop_outputs(out_pin.id);
                             }
                         } else {
                             snarl_state.start_new_wire_out(out_pin.id);
@@ -878,6 +1913,7 @@ impl<T> Snarl<T> {
                         }
                         _ => {}
                     }
+                    let pin_info = mark_compatibility(pin_info, compatible, node_style);
                     draw_pin(ui.painter(), pin_info, pin_pos, pin_size);
                 });
             }
@@ -885,11 +1921,6 @@ impl<T> Snarl<T> {
             ui.expand_to_include_rect(outputs_rect.intersect(payload_clip_rect));
             let outputs_size = outputs_rect.size();
 
-            if !self.nodes.contains(node.0) {
-                // If removed
-                return;
-            }
-
             let mut new_pins_size = vec2(
                 inputs_size.x + outputs_size.x + node_style.spacing.item_spacing.x,
                 f32::max(inputs_size.y, outputs_size.y),
@@ -898,7 +1929,7 @@ impl<T> Snarl<T> {
             let mut pins_bottom = f32::max(inputs_rect.bottom(), outputs_rect.bottom());
 
             // Show body if there's one.
-            if viewer.has_body(&self.nodes.get(node.0).unwrap().value) {
+            if viewer.has_body(value) {
                 let body_left = inputs_rect.right() + node_style.spacing.item_spacing.x;
                 let body_right = outputs_rect.left() - node_style.spacing.item_spacing.x;
                 let body_top = payload_rect.top();
@@ -921,6 +1952,7 @@ impl<T> Snarl<T> {
                     &mut body_ui,
                     snarl_state.scale(),
                     self,
+                    effects,
                 );
 
                 body_rect = body_ui.min_rect();
@@ -932,16 +1964,11 @@ impl<T> Snarl<T> {
                 new_pins_size.y = f32::max(new_pins_size.y, body_size.y);
 
                 pins_bottom = f32::max(pins_bottom, body_rect.bottom());
-
-                if !self.nodes.contains(node.0) {
-                    // If removed
-                    return;
-                }
             }
 
-            if viewer.has_footer(&self.nodes[node.0].value) {
+            if viewer.has_footer(value) {
                 let footer_left = node_rect.left();
-                let footer_right = node_rect.right();
+                let footer_right = payload_right;
                 let footer_top = pins_bottom + node_style.spacing.item_spacing.y;
 
                 let mut footer_rect = Rect::from_min_max(
@@ -965,6 +1992,7 @@ impl<T> Snarl<T> {
                     &mut footer_ui,
                     snarl_state.scale(),
                     self,
+                    effects,
                 );
 
                 footer_rect = footer_ui.min_rect();
@@ -974,11 +2002,6 @@ impl<T> Snarl<T> {
 
                 new_pins_size.x = f32::max(new_pins_size.x, footer_size.x);
                 new_pins_size.y += footer_size.y + node_style.spacing.item_spacing.y;
-
-                if !self.nodes.contains(node.0) {
-                    // If removed
-                    return;
-                }
             }
 
             node_state.set_size(vec2(
@@ -990,15 +2013,11 @@ impl<T> Snarl<T> {
             ));
         });
 
-        if !self.nodes.contains(node.0) {
+        response.layout = node_state.changed_layout();
+        if response.layout.is_some() {
+            // Show node again with the new layout.
             ui.ctx().request_repaint();
-            node_state.clear(ui.ctx());
-            // If removed
-            return response;
         }
-
-        node_state.store(ui.ctx());
-        ui.ctx().request_repaint();
         response
     }
 }
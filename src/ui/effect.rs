@@ -1,46 +1,120 @@
-use std::cell::RefCell;
-
 use egui::Pos2;
 
-use crate::{wire_pins, InPinId, Node, OutPinId, Snarl};
+use crate::{InPinId, NodeId, OutPinId, Snarl, SnarlError};
+
+use super::state::NewWires;
 
+/// Closure executed with mutable reference to the Snarl.
+pub type SnarlClosure<T> = Box<dyn FnOnce(&mut Snarl<T>)>;
+
+/// Deferred mutation of the [`Snarl`].
+///
+/// Effects are queued by [`SnarlViewer`](super::SnarlViewer) methods while the graph is rendered
+/// and applied after rendering is finished.
 pub enum Effect<T> {
     /// Adds a new node to the Snarl.
-    InsertNode { pos: Pos2, node: T },
+    InsertNode {
+        /// Position of the node in graph space.
+        pos: Pos2,
+
+        /// Node value.
+        node: T,
+    },
+
+    /// Adds a new node to the Snarl and connects dragged wires to it.
+    InsertConnectedNode {
+        /// Position of the node in graph space.
+        pos: Pos2,
+
+        /// Node value.
+        node: T,
+
+        /// Wires to connect.
+        wires: NewWires,
+
+        /// Index of the new node's pin that wires are connected to.
+        /// It is input pin for wires dragged from output pins and vice versa.
+        pin: usize,
+    },
 
     /// Removes a node from snarl.
-    RemoveNode { node: NodeId },
+    RemoveNode {
+        /// Node to remove.
+        node: NodeId,
+    },
 
     /// Opens/closes a node.
-    OpenNode { node: NodeId, open: bool },
+    OpenNode {
+        /// Node to open or close.
+        node: NodeId,
+
+        /// Whether node should be open.
+        open: bool,
+    },
 
     /// Adds connection between two nodes.
-    Connect { from: OutPinId, to: InPinId },
+    Connect {
+        /// Output pin of the connection.
+        from: OutPinId,
+
+        /// Input pin of the connection.
+        to: InPinId,
+    },
 
     /// Removes connection between two nodes.
-    Disconnect { from: OutPinId, to: InPinId },
+    Disconnect {
+        /// Output pin of the connection.
+        from: OutPinId,
+
+        /// Input pin of the connection.
+        to: InPinId,
+    },
 
     /// Removes all connections from the output pin.
-    DropOutputs { pin: OutPinId },
+    DropOutputs {
+        /// Output pin to disconnect.
+        pin: OutPinId,
+    },
 
     /// Removes all connections to the input pin.
-    DropInputs { pin: InPinId },
+    DropInputs {
+        /// Input pin to disconnect.
+        pin: InPinId,
+    },
+
+    /// Pastes nodes and wires of another Snarl.
+    Paste {
+        /// Nodes and wires to paste.
+        snarl: Snarl<T>,
+
+        /// Position of the top-left corner of pasted nodes in graph space.
+        pos: Pos2,
+    },
+
+    /// Executes a closure with mutable reference to the node value.
+    EditNode {
+        /// Node to edit.
+        node: NodeId,
+
+        /// Closure that edits the value.
+        f: Box<dyn FnOnce(&mut T)>,
+    },
 
     /// Executes a closure with mutable reference to the Snarl.
-    Closure(Box<dyn FnOnce(&mut Snarl<T>)>),
+    Closure(SnarlClosure<T>),
 }
 
-/// Contained for deferred execution of effects.
-/// It is populated by [`SnarlViewer`] methods and then applied to the Snarl.
+/// Container for deferred execution of effects.
+/// It is populated by [`SnarlViewer`](super::SnarlViewer) methods and then applied to the Snarl.
 pub struct Effects<T> {
-    effects: Vec<Effect<T>>,
+    pub(super) effects: Vec<Effect<T>>,
 }
 
 impl<T> Default for Effects<T> {
     #[inline]
     fn default() -> Self {
         Effects {
-            effects: Default::default(),
+            effects: Vec::new(),
         }
     }
 }
@@ -48,6 +122,7 @@ impl<T> Default for Effects<T> {
 impl<T> Effects<T> {
     #[inline(always)]
     #[doc(hidden)]
+    #[must_use]
     pub fn new() -> Self {
         Effects {
             effects: Vec::new(),
@@ -57,16 +132,43 @@ impl<T> Effects<T> {
     /// Returns `true` if there are no effects.
     /// Returns `false` otherwise.
     #[inline(always)]
+    #[must_use]
     pub fn is_empty(&self) -> bool {
         self.effects.is_empty()
     }
 
+    /// Queues arbitrary effect.
+    #[inline(always)]
+    pub fn push(&mut self, effect: Effect<T>) {
+        self.effects.push(effect);
+    }
+
     /// Inserts a new node to the Snarl.
     #[inline(always)]
     pub fn insert_node(&mut self, pos: Pos2, node: T) {
         self.effects.push(Effect::InsertNode { node, pos });
     }
 
+    /// Inserts a new node to the Snarl and connects dragged wires to its pin.
+    ///
+    /// Wires dragged from output pins are connected to input pin `pin` of the new node
+    /// and wires dragged from input pins are connected to its output pin `pin`.
+    /// When applied by [`Snarl::show`](crate::Snarl::show), wires are connected
+    /// as if they were dropped on that pin when the new node is shown,
+    /// so incompatible wires are skipped and connection limits are respected.
+    /// [`Snarl::try_apply_effect`](crate::Snarl::try_apply_effect) has no viewer
+    /// and connects all wires between existing nodes.
+    /// See [`SnarlViewer::dropped_wire_menu`](super::SnarlViewer::dropped_wire_menu).
+    #[inline(always)]
+    pub fn insert_connected_node(&mut self, pos: Pos2, node: T, wires: &NewWires, pin: usize) {
+        self.effects.push(Effect::InsertConnectedNode {
+            pos,
+            node,
+            wires: wires.clone(),
+            pin,
+        });
+    }
+
     /// Removes a node from the Snarl.
     #[inline(always)]
     pub fn remove_node(&mut self, node: NodeId) {
@@ -91,20 +193,64 @@ impl<T> Effects<T> {
         self.effects.push(Effect::Disconnect { from, to });
     }
 
-    /// Removes all connections from the output pin.
+    /// Removes all connections to the input pin.
     #[inline(always)]
     pub fn drop_inputs(&mut self, pin: InPinId) {
         self.effects.push(Effect::DropInputs { pin });
     }
 
-    /// Removes all connections to the input pin.
+    /// Removes all connections from the output pin.
     #[inline(always)]
     pub fn drop_outputs(&mut self, pin: OutPinId) {
         self.effects.push(Effect::DropOutputs { pin });
     }
+
+    /// Pastes nodes and wires of another Snarl.
+    /// See [`Snarl::paste`].
+    #[inline(always)]
+    pub fn paste(&mut self, snarl: Snarl<T>, pos: Pos2) {
+        self.effects.push(Effect::Paste { snarl, pos });
+    }
+
+    /// Edits node value.
+    ///
+    /// Value is edited with [`Snarl::edit_node`], so the edit is recorded in history if enabled.
+    #[inline(always)]
+    pub fn edit_node(&mut self, node: NodeId, f: impl FnOnce(&mut T) + 'static) {
+        self.effects.push(Effect::EditNode {
+            node,
+            f: Box::new(f),
+        });
+    }
+
+    /// Executes a closure with mutable reference to the Snarl.
+    #[inline(always)]
+    pub fn closure(&mut self, f: impl FnOnce(&mut Snarl<T>) + 'static) {
+        self.effects.push(Effect::Closure(Box::new(f)));
+    }
 }
 
 impl<T> Snarl<T> {
+    /// Applies all effects in order they were queued.
+    ///
+    /// Effects that cannot be applied, for example because they refer
+    /// to nodes that no longer exist, are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use egui_snarl::{Snarl, ui::Effects};
+    /// let mut snarl = Snarl::<i32>::new();
+    /// let node = snarl.insert_node(egui::pos2(0.0, 0.0), 1);
+    ///
+    /// let mut effects = Effects::new();
+    /// effects.edit_node(node, |value| *value += 1);
+    /// effects.insert_node(egui::pos2(100.0, 0.0), 3);
+    ///
+    /// snarl.apply_effects(effects);
+    /// assert_eq!(snarl[node], 2);
+    /// assert_eq!(snarl.nodes().count(), 2);
+    /// ```
     pub fn apply_effects(&mut self, effects: Effects<T>) {
         if effects.effects.is_empty() {
             return;
@@ -114,47 +260,90 @@ impl<T> Snarl<T> {
         }
     }
 
+    /// Applies single effect.
+    ///
+    /// Effect that cannot be applied is ignored.
+    /// Use [`Snarl::try_apply_effect`] to get the error.
     pub fn apply_effect(&mut self, effect: Effect<T>) {
+        // Effects are queued before they are applied,
+        // so they may refer to nodes removed in the meantime.
+        let _ = self.try_apply_effect(effect);
+    }
+
+    /// Applies single effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnarlError`] if the effect refers to nodes that do not exist
+    /// or connects pins that cannot be connected.
+    /// See [`Snarl::try_connect`].
+    /// Wires of [`Effect::InsertConnectedNode`] that cannot be connected are skipped
+    /// and the first error is returned after the rest are connected.
+    pub fn try_apply_effect(&mut self, effect: Effect<T>) -> Result<(), SnarlError> {
         match effect {
             Effect::InsertNode { node, pos } => {
-                let idx = self.nodes.insert(Node {
-                    value: RefCell::new(node),
-                    pos,
-                    open: true,
-                });
-                self.draw_order.push(idx);
+                self.insert_node(pos, node);
             }
-            Effect::RemoveNode { node } => {
-                if self.nodes.contains(node) {
-                    self.remove_node(node);
+            Effect::InsertConnectedNode {
+                pos,
+                node,
+                wires,
+                pin,
+            } => {
+                let node = self.insert_node(pos, node);
+                let wires = match wires {
+                    NewWires::Out(out_pins) => {
+                        let in_pin = InPinId { node, input: pin };
+                        out_pins
+                            .into_iter()
+                            .map(|out_pin| (out_pin, in_pin))
+                            .collect::<Vec<_>>()
+                    }
+                    NewWires::In(in_pins) => {
+                        let out_pin = OutPinId { node, output: pin };
+                        in_pins
+                            .into_iter()
+                            .map(|in_pin| (out_pin, in_pin))
+                            .collect()
+                    }
+                };
+
+                // Failed wires are skipped, so the node gets all wires that can be connected.
+                let mut result = Ok(());
+                for (from, to) in wires {
+                    if let Err(err) = self.try_connect(from, to) {
+                        result = result.and(Err(err));
+                    }
                 }
+                return result;
+            }
+            Effect::RemoveNode { node } => {
+                self.try_remove_node(node)?;
             }
             Effect::OpenNode { node, open } => {
-                if self.nodes.contains(node) {
-                    self.nodes[node].open = open;
-                }
+                self.try_open_node(node, open)?;
             }
             Effect::Connect { from, to } => {
-                if self.nodes.contains(from.node) && self.nodes.contains(to.node) {
-                    self.wires.insert(wire_pins(from, to));
-                }
+                self.try_connect(from, to)?;
             }
             Effect::Disconnect { from, to } => {
-                if self.nodes.contains(from.node) && self.nodes.contains(to.node) {
-                    self.wires.remove(&wire_pins(from, to));
-                }
+                self.try_disconnect(from, to)?;
             }
             Effect::DropOutputs { pin } => {
-                if self.nodes.contains(pin.node) {
-                    self.wires.drop_outputs(pin);
-                }
+                self.try_drop_outputs(pin)?;
             }
             Effect::DropInputs { pin } => {
-                if self.nodes.contains(pin.node) {
-                    self.wires.drop_inputs(pin);
-                }
+                self.try_drop_inputs(pin)?;
+            }
+            Effect::Paste { snarl, pos } => {
+                self.paste(snarl, pos);
+            }
+            Effect::EditNode { node, f } => {
+                let value = self.edit_node(node).ok_or(SnarlError::UnknownNode(node))?;
+                f(value);
             }
             Effect::Closure(f) => f(self),
         }
+        Ok(())
     }
 }
@@ -2,9 +2,13 @@ use egui::{epaint::PathShape, vec2, Color32, Painter, Pos2, Rect, Shape, Stroke,
 
 use crate::{InPinId, OutPinId};
 
+/// Either input or output pin.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AnyPin {
+    /// Output pin.
     Out(OutPinId),
+
+    /// Input pin.
     In(InPinId),
 }
 
@@ -51,6 +55,11 @@ pub struct PinInfo {
 
     /// Outline stroke of the pin.
     pub stroke: Stroke,
+
+    /// Maximum number of wires connected to the pin.
+    /// Connecting more wires in UI replaces the oldest ones.
+    /// `None` means unlimited.
+    pub max_connections: Option<usize>,
 }
 
 impl Default for PinInfo {
@@ -60,6 +69,7 @@ impl Default for PinInfo {
             size: 1.0,
             fill: Color32::GRAY,
             stroke: Stroke::new(1.0, Color32::BLACK),
+            max_connections: None,
         }
     }
 }
@@ -89,6 +99,12 @@ impl PinInfo {
         self
     }
 
+    /// Sets the maximum number of wires connected to the pin.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
     /// Creates a circle pin.
     pub fn circle() -> Self {
         PinInfo {
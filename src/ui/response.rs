@@ -0,0 +1,56 @@
+use egui::{Id, Pos2, Response};
+
+use crate::{InPinId, NodeId, OutPinId, SnarlError};
+
+use super::AnyPin;
+
+/// Result of [`Snarl::show`](crate::Snarl::show).
+///
+/// Describes what happened with the graph during the frame.
+#[derive(Clone, Debug)]
+pub struct SnarlResponse {
+    /// Response of the graph background.
+    pub response: Response,
+
+    /// Id of the Snarl widget resolved from `id_source` and the parent [`Ui`](egui::Ui).
+    /// Pass it to [`SnarlViewport::new`](super::SnarlViewport::new) to control the viewport.
+    pub id: Id,
+
+    /// Topmost node under the pointer.
+    pub hovered_node: Option<NodeId>,
+
+    /// Pin under the pointer.
+    pub hovered_pin: Option<AnyPin>,
+
+    /// Wire under the pointer.
+    pub hovered_wire: Option<(OutPinId, InPinId)>,
+
+    /// Nodes moved by dragging.
+    pub moved_nodes: Vec<NodeId>,
+
+    /// Node clicked with primary button.
+    pub clicked_node: Option<NodeId>,
+
+    /// Node double-clicked with primary button.
+    pub double_clicked_node: Option<NodeId>,
+
+    /// Wires added to the Snarl.
+    pub wires_created: Vec<(OutPinId, InPinId)>,
+
+    /// Wires removed from the Snarl.
+    pub wires_removed: Vec<(OutPinId, InPinId)>,
+
+    /// Errors of effects queued by the viewer that could not be applied.
+    /// Such effects are skipped and do not change the Snarl.
+    pub effect_errors: Vec<SnarlError>,
+
+    /// Whether the set of selected nodes changed.
+    pub selection_changed: bool,
+
+    /// Whether nodes, wires or their reroute points were modified by user interaction,
+    /// viewer effects or undo/redo.
+    pub changed: bool,
+
+    /// Pointer position in graph space if pointer is over the graph.
+    pub pointer_pos: Option<Pos2>,
+}
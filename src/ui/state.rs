@@ -1,79 +1,75 @@
-use egui::{style::Spacing, Align, Context, Id, Pos2, Rect, Vec2};
+use egui::{emath::lerp, style::Spacing, Align, Context, Id, Pos2, Rect, Vec2};
 
-use crate::{InPinId, OutPinId, Snarl};
+use crate::{InPinId, NodeLayout, OutPinId, Snarl};
 
-use super::SnarlStyle;
+use super::{AnyPin, SnarlStyle};
 
 /// Node UI state.
-
 pub struct NodeState {
     /// Node size for this frame.
     /// It is updated to fit content.
     size: Vec2,
     header_height: f32,
+    header_width: f32,
     body_width: f32,
     footer_width: f32,
 
-    id: Id,
     scale: f32,
     dirty: bool,
 }
 
-#[derive(Clone, Copy, PartialEq)]
-struct NodeData {
-    unscaled_size: Vec2,
-    unscaled_header_height: f32,
-    unscaled_body_width: f32,
-    unsacled_footer_width: f32,
-}
-
 impl NodeState {
-    pub fn load(cx: &Context, id: Id, spacing: &Spacing, scale: f32) -> Self {
-        match cx.data_mut(|d| d.get_temp::<NodeData>(id)) {
-            Some(data) => NodeState {
-                size: data.unscaled_size * scale,
-                header_height: data.unscaled_header_height * scale,
-                body_width: data.unscaled_body_width * scale,
-                footer_width: data.unsacled_footer_width * scale,
-                id,
+    /// Loads state from layout measured in previous frames.
+    pub fn load(layout: Option<NodeLayout>, spacing: &Spacing, scale: f32) -> Self {
+        match layout {
+            Some(layout) => NodeState {
+                size: layout.size * scale,
+                header_height: layout.header_height * scale,
+                header_width: layout.header_width * scale,
+                body_width: layout.body_width * scale,
+                footer_width: layout.footer_width * scale,
                 scale,
                 dirty: false,
             },
-            None => Self::initial(id, spacing, scale),
+            None => Self::initial(spacing, scale),
         }
     }
 
-    pub fn clear(self, cx: &Context) {
-        cx.data_mut(|d| d.remove::<Self>(self.id));
-    }
-
-    pub fn store(&self, cx: &Context) {
-        if self.dirty {
-            cx.data_mut(|d| {
-                d.insert_temp(
-                    self.id,
-                    NodeData {
-                        unscaled_size: self.size / self.scale,
-                        unscaled_header_height: self.header_height / self.scale,
-                        unscaled_body_width: self.body_width / self.scale,
-                        unsacled_footer_width: self.footer_width / self.scale,
-                    },
-                )
-            });
+    /// Returns layout to store in the Snarl if it was changed.
+    pub fn changed_layout(&self) -> Option<NodeLayout> {
+        if !self.dirty {
+            return None;
         }
+        Some(NodeLayout {
+            size: self.size / self.scale,
+            header_height: self.header_height / self.scale,
+            header_width: self.header_width / self.scale,
+            body_width: self.body_width / self.scale,
+            footer_width: self.footer_width / self.scale,
+        })
     }
 
     /// Finds node rect at specific position (excluding node frame margin).
+    /// Collapsed node is as wide as its header.
     pub fn node_rect(&self, pos: Pos2, openness: f32) -> Rect {
         Rect::from_min_size(
             pos,
             egui::vec2(
-                self.size.x,
+                lerp(
+                    f32::min(self.header_width, self.size.x)..=self.size.x,
+                    openness,
+                ),
                 f32::max(self.header_height, self.size.y * openness),
             ),
         )
     }
 
+    /// Returns width of the open node.
+    /// Pins, body and footer are laid out in this width even when node is collapsed.
+    pub fn open_width(&self) -> f32 {
+        self.size.x
+    }
+
     pub fn payload_offset(&self, openness: f32) -> f32 {
         (self.size.y) * (1.0 - openness)
     }
@@ -102,6 +98,13 @@ impl NodeState {
         }
     }
 
+    pub fn set_header_width(&mut self, width: f32) {
+        if self.header_width != width {
+            self.header_width = width;
+            self.dirty = true;
+        }
+    }
+
     pub fn set_body_width(&mut self, width: f32) {
         if self.body_width != width {
             self.body_width = width;
@@ -116,22 +119,44 @@ impl NodeState {
         }
     }
 
-    fn initial(id: Id, spacing: &Spacing, scale: f32) -> Self {
+    fn initial(spacing: &Spacing, scale: f32) -> Self {
         NodeState {
             size: spacing.interact_size,
             header_height: spacing.interact_size.y,
+            header_width: spacing.interact_size.x,
             body_width: 0.0,
             footer_width: 0.0,
-            id,
             dirty: true,
             scale,
         }
     }
 }
 
-#[derive(Clone)]
+/// Duration of animated viewport transitions in seconds.
+const VIEW_ANIMATION_TIME: f32 = 0.3;
+
+/// Animated transition of the viewport.
+#[derive(Clone, Copy)]
+struct ViewAnimation {
+    /// Graph position at the viewport center when animation started.
+    from_center: Pos2,
+    from_scale: f32,
+
+    /// Graph position at the viewport center when animation ends.
+    to_center: Pos2,
+    to_scale: f32,
+
+    /// Time when animation started.
+    start: f64,
+}
+
+/// Wires that are being dragged from pins and are not connected yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum NewWires {
+    /// Wires dragged from input pins, looking for an output pin.
     In(Vec<InPinId>),
+
+    /// Wires dragged from output pins, looking for an input pin.
     Out(Vec<OutPinId>),
 }
 
@@ -146,6 +171,17 @@ pub struct SnarlState {
 
     new_wires: Option<NewWires>,
 
+    /// Corners of the rectangle selection in graph space.
+    rect_selection: Option<(Pos2, Pos2)>,
+
+    animation: Option<ViewAnimation>,
+
+    /// Wire which context menu is shown.
+    wire_menu: Option<(OutPinId, InPinId)>,
+
+    /// Wires dropped on empty space and position in graph space where menu for them is shown.
+    dropped_wires: Option<(Pos2, NewWires)>,
+
     id: Id,
 
     /// Flag indicating that the graph state is dirty must be saved.
@@ -158,6 +194,10 @@ struct SnarlStateData {
     scale: f32,
     target_scale: f32,
     new_wires: Option<NewWires>,
+    rect_selection: Option<(Pos2, Pos2)>,
+    animation: Option<ViewAnimation>,
+    wire_menu: Option<(OutPinId, InPinId)>,
+    dropped_wires: Option<(Pos2, NewWires)>,
 }
 
 impl SnarlState {
@@ -173,9 +213,32 @@ impl SnarlState {
             return Self::initial(id, viewport, snarl, style);
         };
 
+        let mut dirty = false;
+
+        if let Some(animation) = data.animation {
+            let t = (cx.input(|i| i.time) - animation.start) as f32 / VIEW_ANIMATION_TIME;
+            let t = t.clamp(0.0, 1.0);
+            // Smoothstep easing.
+            let t = t * t * (3.0 - 2.0 * t);
+
+            let center = animation.from_center.lerp(animation.to_center, t);
+            data.scale = lerp(animation.from_scale..=animation.to_scale, t);
+            data.target_scale = data.scale;
+            data.offset = center.to_vec2() * data.scale;
+
+            // Keep zoom animation in sync.
+            cx.animate_value_with_time(id.with("zoom-scale"), data.scale, 0.0);
+
+            if t < 1.0 {
+                cx.request_repaint();
+            } else {
+                data.animation = None;
+            }
+            dirty = true;
+        }
+
         let new_scale = cx.animate_value_with_time(id.with("zoom-scale"), data.target_scale, 0.1);
 
-        let mut dirty = false;
         if new_scale != data.scale {
             let a = pivot + data.offset - viewport.center().to_vec2();
 
@@ -189,6 +252,10 @@ impl SnarlState {
             scale: data.scale,
             target_scale: data.target_scale,
             new_wires: data.new_wires,
+            rect_selection: data.rect_selection,
+            animation: data.animation,
+            wire_menu: data.wire_menu,
+            dropped_wires: data.dropped_wires,
             id,
             dirty,
         }
@@ -209,6 +276,10 @@ impl SnarlState {
                 scale,
                 target_scale: scale,
                 new_wires: None,
+                rect_selection: None,
+                animation: None,
+                wire_menu: None,
+                dropped_wires: None,
                 id,
                 dirty: true,
             };
@@ -216,14 +287,7 @@ impl SnarlState {
 
         bb = bb.expand(100.0);
 
-        let bb_size = bb.size();
-        let viewport_size = viewport.size();
-
-        let scale = (viewport_size.x / bb_size.x)
-            .min(1.0)
-            .min(viewport_size.y / bb_size.y)
-            .min(style.max_scale)
-            .max(style.min_scale);
+        let scale = fit_scale(bb, viewport, style);
 
         let offset = bb.center().to_vec2() * scale;
 
@@ -232,6 +296,10 @@ impl SnarlState {
             scale,
             target_scale: scale,
             new_wires: None,
+            rect_selection: None,
+            animation: None,
+            wire_menu: None,
+            dropped_wires: None,
             id,
             dirty: true,
         }
@@ -248,6 +316,10 @@ impl SnarlState {
                         scale: self.scale,
                         target_scale: self.target_scale,
                         new_wires: self.new_wires,
+                        rect_selection: self.rect_selection,
+                        animation: self.animation,
+                        wire_menu: self.wire_menu,
+                        dropped_wires: self.dropped_wires,
                     },
                 )
             });
@@ -257,6 +329,7 @@ impl SnarlState {
     #[inline(always)]
     pub fn pan(&mut self, delta: Vec2) {
         self.offset += delta;
+        self.animation = None;
         self.dirty = true;
     }
 
@@ -273,6 +346,7 @@ impl SnarlState {
     #[inline(always)]
     pub fn set_scale(&mut self, scale: f32) {
         self.target_scale = scale;
+        self.animation = None;
         self.dirty = true;
     }
 
@@ -375,8 +449,121 @@ impl SnarlState {
         self.new_wires.take()
     }
 
+    pub fn start_rect_selection(&mut self, pos: Pos2) {
+        self.rect_selection = Some((pos, pos));
+        self.dirty = true;
+    }
+
+    pub fn update_rect_selection(&mut self, pos: Pos2) {
+        if let Some((_, end)) = &mut self.rect_selection {
+            *end = pos;
+            self.dirty = true;
+        }
+    }
+
+    pub fn stop_rect_selection(&mut self) {
+        self.dirty |= self.rect_selection.is_some();
+        self.rect_selection = None;
+    }
+
+    pub fn has_rect_selection(&self) -> bool {
+        self.rect_selection.is_some()
+    }
+
+    /// Returns rectangle selection in screen space.
+    pub fn rect_selection(&self, viewport: Rect) -> Option<Rect> {
+        let (start, end) = self.rect_selection?;
+        Some(Rect::from_two_pos(
+            self.graph_pos_to_screen(start, viewport),
+            self.graph_pos_to_screen(end, viewport),
+        ))
+    }
+
+    pub fn wire_menu(&self) -> Option<(OutPinId, InPinId)> {
+        self.wire_menu
+    }
+
+    pub fn set_wire_menu(&mut self, wire: Option<(OutPinId, InPinId)>) {
+        if self.wire_menu != wire {
+            self.wire_menu = wire;
+            self.dirty = true;
+        }
+    }
+
+    pub fn dropped_wires(&self) -> Option<&(Pos2, NewWires)> {
+        self.dropped_wires.as_ref()
+    }
+
+    pub fn set_dropped_wires(&mut self, dropped: Option<(Pos2, NewWires)>) {
+        self.dirty |= self.dropped_wires.is_some() || dropped.is_some();
+        self.dropped_wires = dropped;
+    }
+
     pub fn set_offset(&mut self, offset: Vec2) {
         self.offset = offset;
+        self.animation = None;
+        self.dirty = true;
+    }
+
+    /// Sets scale immediately, without zoom animation.
+    /// Graph position at the viewport center is preserved.
+    pub fn set_scale_now(&mut self, cx: &Context, scale: f32) {
+        self.offset = self.offset / self.scale * scale;
+        self.scale = scale;
+        self.target_scale = scale;
+        self.animation = None;
         self.dirty = true;
+        cx.animate_value_with_time(self.id.with("zoom-scale"), scale, 0.0);
+    }
+
+    /// Starts animated transition of the viewport
+    /// to given graph position at the center and scale.
+    pub fn animate_to(&mut self, cx: &Context, center: Pos2, scale: f32) {
+        self.animation = Some(ViewAnimation {
+            from_center: (self.offset / self.scale).to_pos2(),
+            from_scale: self.scale,
+            to_center: center,
+            to_scale: scale,
+            start: cx.input(|i| i.time),
+        });
+        self.dirty = true;
+        cx.request_repaint();
     }
 }
+
+/// Returns scale at which the rectangle in graph space fits the viewport.
+pub fn fit_scale(rect: Rect, viewport: Rect, style: &SnarlStyle) -> f32 {
+    let rect_size = rect.size();
+    let viewport_size = viewport.size();
+
+    (viewport_size.x / rect_size.x)
+        .min(1.0)
+        .min(viewport_size.y / rect_size.y)
+        .min(style.max_scale)
+        .max(style.min_scale)
+}
+
+/// Queues wires to connect to the `pin` of inserted node when the node is shown next time,
+/// so that its pin info is known.
+pub fn push_pending_wires(cx: &Context, id: Id, wires: NewWires, pin: AnyPin) {
+    cx.data_mut(|d| {
+        d.get_temp_mut_or_default::<Vec<(NewWires, AnyPin)>>(pending_wires_id(id))
+            .push((wires, pin));
+    });
+}
+
+/// Takes wires queued with [`push_pending_wires`].
+pub fn take_pending_wires(cx: &Context, id: Id) -> Vec<(NewWires, AnyPin)> {
+    cx.data_mut(|d| d.remove_temp::<Vec<(NewWires, AnyPin)>>(pending_wires_id(id)))
+        .unwrap_or_default()
+}
+
+fn pending_wires_id(id: Id) -> Id {
+    id.with("snarl-pending-wires")
+}
+
+/// Graph offset and scale of the viewport as seen by the last frame.
+pub fn view_of(cx: &Context, id: Id) -> Option<(Vec2, f32)> {
+    cx.data_mut(|d| d.get_temp::<SnarlStateData>(id))
+        .map(|data| (data.offset, data.scale))
+}
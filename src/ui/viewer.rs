@@ -1,13 +1,22 @@
-use egui::{Color32, Pos2, Style, Ui};
+use egui::{Color32, PointerButton, Pos2, Style, Ui};
 
-use crate::{InPin, NodeId, OutPin, Snarl};
+use crate::{InPin, InPinId, NodeId, OutPin, OutPinId, Snarl};
 
-use super::pin::PinInfo;
+use super::{
+    effect::Effects,
+    pin::PinInfo,
+    state::NewWires,
+    wire::{WireInfo, WireStyle},
+};
 
 /// SnarlViewer is a trait for viewing a Snarl.
 ///
 /// It can extract necessary data from the nodes and controls their
 /// response to certain events.
+///
+/// Methods receive shared reference to the [`Snarl`] being rendered.
+/// Any mutation, including editing node values, is queued into [`Effects`]
+/// and applied after the whole graph is rendered.
 pub trait SnarlViewer<T> {
     /// Returns title of the node.
     fn title(&mut self, node: &T) -> String;
@@ -31,6 +40,7 @@ pub trait SnarlViewer<T> {
     }
 
     /// Renders the node's header.
+    #[allow(clippy::too_many_arguments)]
     fn show_header(
         &mut self,
         node: NodeId,
@@ -38,9 +48,10 @@ pub trait SnarlViewer<T> {
         outputs: &[OutPin],
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) {
-        let _ = (inputs, outputs, scale);
+        let _ = (inputs, outputs, scale, effects);
         ui.label(self.title(&snarl[node]));
     }
 
@@ -51,8 +62,14 @@ pub trait SnarlViewer<T> {
     fn inputs(&mut self, node: &T) -> usize;
 
     /// Renders the node's input pin.
-    fn show_input(&mut self, pin: &InPin, ui: &mut Ui, scale: f32, snarl: &mut Snarl<T>)
-        -> PinInfo;
+    fn show_input(
+        &mut self,
+        pin: &InPin,
+        ui: &mut Ui,
+        scale: f32,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) -> PinInfo;
 
     /// Renders the node's output pin.
     fn show_output(
@@ -60,10 +77,12 @@ pub trait SnarlViewer<T> {
         pin: &OutPin,
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) -> PinInfo;
 
     /// Renders the node's body.
+    #[allow(clippy::too_many_arguments)]
     fn show_body(
         &mut self,
         node: NodeId,
@@ -71,12 +90,14 @@ pub trait SnarlViewer<T> {
         outputs: &[OutPin],
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) {
-        let _ = (node, inputs, outputs, ui, scale, snarl);
+        let _ = (node, inputs, outputs, ui, scale, snarl, effects);
     }
 
     /// Renders the node's footer.
+    #[allow(clippy::too_many_arguments)]
     fn show_footer(
         &mut self,
         node: NodeId,
@@ -84,12 +105,14 @@ pub trait SnarlViewer<T> {
         outputs: &[OutPin],
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) {
-        let _ = (node, inputs, outputs, ui, scale, snarl);
+        let _ = (node, inputs, outputs, ui, scale, snarl, effects);
     }
 
     /// Renders the node's on-hover popup.
+    #[allow(clippy::too_many_arguments)]
     fn show_on_hover_popup(
         &mut self,
         node: NodeId,
@@ -97,29 +120,64 @@ pub trait SnarlViewer<T> {
         outputs: &[OutPin],
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) {
-        let _ = (node, inputs, outputs, ui, scale, snarl);
+        let _ = (node, inputs, outputs, ui, scale, snarl, effects);
     }
 
     /// Returns color of the node's input pin.
     /// Called when pin in not visible.
-    fn input_color(&mut self, pin: &InPin, style: &Style, snarl: &mut Snarl<T>) -> Color32;
+    fn input_color(&mut self, pin: &InPin, style: &Style, snarl: &Snarl<T>) -> Color32;
 
     /// Returns color of the node's output pin.
     /// Called when pin in not visible.
-    fn output_color(&mut self, pin: &OutPin, style: &Style, snarl: &mut Snarl<T>) -> Color32;
+    fn output_color(&mut self, pin: &OutPin, style: &Style, snarl: &Snarl<T>) -> Color32;
 
     /// Show context menu for the snarl.
     ///
     /// This can be used to implement menu for adding new nodes.
-    fn graph_menu(&mut self, pos: Pos2, ui: &mut Ui, scale: f32, snarl: &mut Snarl<T>) {
-        let _ = (pos, ui, scale, snarl);
+    fn graph_menu(
+        &mut self,
+        pos: Pos2,
+        ui: &mut Ui,
+        scale: f32,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        let _ = (pos, ui, scale, snarl, effects);
+    }
+
+    /// Checks if the viewer wants to show a menu when new wires are dropped on empty space.
+    fn has_dropped_wire_menu(&mut self, src_pins: &NewWires, snarl: &Snarl<T>) -> bool {
+        let _ = (src_pins, snarl);
+        false
+    }
+
+    /// Show menu for new wires dropped on empty space.
+    ///
+    /// `pos` is the position in graph space where wires were dropped.
+    /// This can be used to implement menu for adding new nodes
+    /// that are connected to the wires with [`Effects::insert_connected_node`].
+    ///
+    /// Menu is closed when any effect is queued.
+    #[allow(clippy::too_many_arguments)]
+    fn dropped_wire_menu(
+        &mut self,
+        pos: Pos2,
+        src_pins: &NewWires,
+        ui: &mut Ui,
+        scale: f32,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        let _ = (pos, src_pins, ui, scale, snarl, effects);
     }
 
     /// Show context menu for the snarl.
     ///
     /// This can be used to implement menu for adding new nodes.
+    #[allow(clippy::too_many_arguments)]
     fn node_menu(
         &mut self,
         node: NodeId,
@@ -127,41 +185,217 @@ pub trait SnarlViewer<T> {
         outputs: &[OutPin],
         ui: &mut Ui,
         scale: f32,
-        snarl: &mut Snarl<T>,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
     ) {
-        let _ = (node, inputs, outputs, ui, scale, snarl);
+        let _ = (node, inputs, outputs, ui, scale, snarl, effects);
+    }
+
+    /// Checks if the output pin can be connected to the input pin.
+    ///
+    /// While user drags new wire, pins it can't be connected to are greyed out
+    /// and pins it can be connected to are highlighted.
+    /// Wire dropped on incompatible pin is discarded without calling [`SnarlViewer::connect`].
+    ///
+    /// By default any pins can be connected.
+    #[inline]
+    fn can_connect(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>) -> bool {
+        let _ = (from, to, snarl);
+        true
+    }
+
+    /// Returns shape of the wire between two pins.
+    ///
+    /// By default [`SnarlStyle::wire_style`](super::SnarlStyle::wire_style) is used for all wires.
+    #[inline]
+    fn wire_style(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>) -> Option<WireStyle> {
+        let _ = (from, to, snarl);
+        None
+    }
+
+    /// Returns look of the wire between two pins.
+    ///
+    /// This can be used to color different kinds of wires differently
+    /// or to highlight wires carrying errors.
+    /// By default wires are colored by their pins and styled by [`SnarlStyle`](super::SnarlStyle).
+    #[inline]
+    fn wire_info(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>) -> WireInfo {
+        let _ = (from, to, snarl);
+        WireInfo::default()
+    }
+
+    /// Checks if wire has something to show in on-hover popup.
+    #[inline]
+    fn has_wire_hover_ui(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>) -> bool {
+        let _ = (from, to, snarl);
+        false
+    }
+
+    /// Renders the wire's on-hover popup.
+    ///
+    /// This can be used to show the value carried by the wire.
+    #[allow(clippy::too_many_arguments)]
+    fn wire_hover_ui(
+        &mut self,
+        from: &OutPin,
+        to: &InPin,
+        ui: &mut Ui,
+        scale: f32,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        let _ = (from, to, ui, scale, snarl, effects);
+    }
+
+    /// Checks if wire has context menu.
+    ///
+    /// If it does, right-clicking the wire opens [`SnarlViewer::wire_menu`]
+    /// instead of calling [`SnarlViewer::wire_clicked`].
+    #[inline]
+    fn has_wire_menu(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>) -> bool {
+        let _ = (from, to, snarl);
+        false
+    }
+
+    /// Show context menu for the wire.
+    ///
+    /// This can be used to offer actions like disconnecting the wire or inserting a node into it.
+    #[allow(clippy::too_many_arguments)]
+    fn wire_menu(
+        &mut self,
+        from: &OutPin,
+        to: &InPin,
+        ui: &mut Ui,
+        scale: f32,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        let _ = (from, to, ui, scale, snarl, effects);
+    }
+
+    /// Notifies the viewer that the wire was clicked.
+    ///
+    /// By default right-clicking the wire disconnects it with [`SnarlViewer::disconnect`].
+    #[inline]
+    fn wire_clicked(
+        &mut self,
+        from: &OutPin,
+        to: &InPin,
+        button: PointerButton,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        if button == PointerButton::Secondary {
+            self.disconnect(from, to, snarl, effects);
+        }
+    }
+
+    /// Chooses pins of the `node` to splice it into the wire when the node is dropped on it.
+    ///
+    /// Returns indices of the input and output pins of the node.
+    /// Wire is then split with [`SnarlViewer::disconnect`] and [`SnarlViewer::connect`],
+    /// so that `from` is connected to the input pin and the output pin is connected to `to`.
+    /// Returns `None` if the node can't be spliced into the wire.
+    ///
+    /// Pins that can't be connected according to [`SnarlViewer::can_connect`]
+    /// or connection limits are rejected and the wire is left intact.
+    ///
+    /// By default first input and output pins without wires
+    /// that can be connected to `from` and `to` are chosen.
+    fn splice_pins(
+        &mut self,
+        node: NodeId,
+        from: &OutPin,
+        to: &InPin,
+        snarl: &Snarl<T>,
+    ) -> Option<(usize, usize)> {
+        let input = (0..self.inputs(&snarl[node])).find(|&input| {
+            let pin = snarl.in_pin(InPinId { node, input });
+            pin.remotes.is_empty() && self.can_connect(from, &pin, snarl)
+        })?;
+        let output = (0..self.outputs(&snarl[node])).find(|&output| {
+            let pin = snarl.out_pin(OutPinId { node, output });
+            pin.remotes.is_empty() && self.can_connect(&pin, to, snarl)
+        })?;
+        Some((input, output))
     }
 
     /// Asks the viewer to connect two pins.
     ///
     /// This is usually happens when user drags a wire from one node's output pin to another node's input pin or vice versa.
-    /// By default this method connects the pins and returns `Ok(())`.
+    /// By default this method queues connection of the pins.
     #[inline]
-    fn connect(&mut self, from: &OutPin, to: &InPin, snarl: &mut Snarl<T>) {
-        snarl.connect(from.id, to.id);
+    fn connect(&mut self, from: &OutPin, to: &InPin, snarl: &Snarl<T>, effects: &mut Effects<T>) {
+        let _ = snarl;
+        effects.connect(from.id, to.id);
     }
 
     /// Asks the viewer to disconnect two pins.
+    ///
+    /// By default this method queues disconnection of the pins.
     #[inline]
-    fn disconnect(&mut self, from: &OutPin, to: &InPin, snarl: &mut Snarl<T>) {
-        snarl.disconnect(from.id, to.id);
+    fn disconnect(
+        &mut self,
+        from: &OutPin,
+        to: &InPin,
+        snarl: &Snarl<T>,
+        effects: &mut Effects<T>,
+    ) {
+        let _ = snarl;
+        effects.disconnect(from.id, to.id);
     }
 
     /// Asks the viewer to disconnect all wires from the output pin.
     ///
     /// This is usually happens when right-clicking on an output pin.
-    /// By default this method disconnects the pins and returns `Ok(())`.
+    /// By default this method queues disconnection of all wires from the pin.
     #[inline]
-    fn drop_outputs(&mut self, pin: &OutPin, snarl: &mut Snarl<T>) {
-        snarl.drop_outputs(pin.id);
+    fn drop_outputs(&mut self, pin: &OutPin, snarl: &Snarl<T>, effects: &mut Effects<T>) {
+        let _ = snarl;
+        effects.drop_outputs(pin.id);
     }
 
     /// Asks the viewer to disconnect all wires from the input pin.
     ///
     /// This is usually happens when right-clicking on an input pin.
-    /// By default this method disconnects the pins and returns `Ok(())`.
+    /// By default this method queues disconnection of all wires from the pin.
+    #[inline]
+    fn drop_inputs(&mut self, pin: &InPin, snarl: &Snarl<T>, effects: &mut Effects<T>) {
+        let _ = snarl;
+        effects.drop_inputs(pin.id);
+    }
+
+    /// Asks the viewer to copy nodes.
+    ///
+    /// This happens when user copies (Ctrl+C), cuts (Ctrl+X) or duplicates (Ctrl+D) selected nodes.
+    /// Returned Snarl is put into clipboard using [`SnarlViewer::clipboard_text`]
+    /// or pasted next to the original nodes when duplicating.
+    /// Use [`Snarl::copy_nodes`] to implement it.
+    ///
+    /// By default nodes are not copied.
+    #[inline]
+    fn copy_nodes(&mut self, nodes: &[NodeId], snarl: &Snarl<T>) -> Option<Snarl<T>> {
+        let _ = (nodes, snarl);
+        None
+    }
+
+    /// Converts copied nodes into text to put into system clipboard.
+    ///
+    /// With `serde` feature use [`Snarl::to_clipboard_text`] to implement it.
+    /// By default copied nodes are not put into clipboard.
+    #[inline]
+    fn clipboard_text(&mut self, clip: &Snarl<T>) -> Option<String> {
+        let _ = clip;
+        None
+    }
+
+    /// Converts text pasted from system clipboard into nodes to paste.
+    ///
+    /// With `serde` feature use [`Snarl::from_clipboard_text`] to implement it.
+    /// By default pasted text is ignored.
     #[inline]
-    fn drop_inputs(&mut self, pin: &InPin, snarl: &mut Snarl<T>) {
-        snarl.drop_inputs(pin.id);
+    fn parse_clipboard_text(&mut self, text: &str) -> Option<Snarl<T>> {
+        let _ = text;
+        None
     }
 }
@@ -0,0 +1,113 @@
+use egui::{Context, Id, Vec2};
+
+use crate::NodeId;
+
+use super::state::view_of;
+
+/// Change of the viewport requested through [`SnarlViewport`].
+/// Requests are applied by the next [`Snarl::show`](crate::Snarl::show).
+#[derive(Clone, Debug)]
+pub(super) enum ViewRequest {
+    Offset(Vec2),
+    Scale(f32),
+
+    /// Fit given nodes or all nodes if `None`.
+    Fit(Option<Vec<NodeId>>),
+    Center(NodeId),
+}
+
+/// Handle to the viewport of the Snarl widget.
+///
+/// Viewport is identified by the [`Id`] of the Snarl widget returned in [`SnarlResponse::id`](super::SnarlResponse::id).
+/// It is derived from `id_source` passed to [`Snarl::show`](crate::Snarl::show) and the id of the parent [`Ui`](egui::Ui),
+/// same as `ui.make_persistent_id(id_source)`.
+/// Stored id lets the viewport be controlled from anywhere in the app, for example from a side panel
+/// that searches nodes.
+///
+/// Changes are applied when the Snarl is shown next time.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_snarl::{NodeId, ui::SnarlViewport};
+/// fn focus_node(ctx: &egui::Context, snarl_id: egui::Id, node: NodeId) {
+///     let viewport = SnarlViewport::new(snarl_id);
+///     viewport.set_scale(ctx, 1.0);
+///     viewport.center_node(ctx, node);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnarlViewport {
+    id: Id,
+}
+
+impl SnarlViewport {
+    /// Returns handle to the viewport of the Snarl widget with given `id`.
+    /// See [`SnarlResponse::id`](super::SnarlResponse::id).
+    #[must_use]
+    pub fn new(id: Id) -> Self {
+        SnarlViewport { id }
+    }
+
+    /// Returns offset of the viewport.
+    ///
+    /// This is position in graph space at the center of the viewport multiplied by scale.
+    /// Returns `None` if the Snarl was not shown yet.
+    #[must_use]
+    pub fn offset(&self, ctx: &Context) -> Option<Vec2> {
+        view_of(ctx, self.id).map(|(offset, _)| offset)
+    }
+
+    /// Returns scale of the viewport.
+    /// Returns `None` if the Snarl was not shown yet.
+    #[must_use]
+    pub fn scale(&self, ctx: &Context) -> Option<f32> {
+        view_of(ctx, self.id).map(|(_, scale)| scale)
+    }
+
+    /// Sets offset of the viewport.
+    /// See [`SnarlViewport::offset`].
+    pub fn set_offset(&self, ctx: &Context, offset: Vec2) {
+        self.request(ctx, ViewRequest::Offset(offset));
+    }
+
+    /// Sets scale of the viewport, keeping its center in place.
+    /// Scale is clamped to the range allowed by [`SnarlStyle`](super::SnarlStyle).
+    pub fn set_scale(&self, ctx: &Context, scale: f32) {
+        self.request(ctx, ViewRequest::Scale(scale));
+    }
+
+    /// Animates the viewport to fit all nodes.
+    pub fn fit_all(&self, ctx: &Context) {
+        self.request(ctx, ViewRequest::Fit(None));
+    }
+
+    /// Animates the viewport to fit given nodes.
+    /// Nodes that do not exist are ignored.
+    pub fn fit_nodes(&self, ctx: &Context, nodes: impl IntoIterator<Item = NodeId>) {
+        self.request(ctx, ViewRequest::Fit(Some(nodes.into_iter().collect())));
+    }
+
+    /// Animates the viewport to center the node, keeping current scale.
+    pub fn center_node(&self, ctx: &Context, node: NodeId) {
+        self.request(ctx, ViewRequest::Center(node));
+    }
+
+    fn request(&self, ctx: &Context, request: ViewRequest) {
+        ctx.data_mut(|d| {
+            d.get_temp_mut_or_default::<Vec<ViewRequest>>(Self::requests_id(self.id))
+                .push(request);
+        });
+        ctx.request_repaint();
+    }
+
+    /// Takes requests queued for the Snarl with given id.
+    pub(super) fn take_requests(ctx: &Context, id: Id) -> Vec<ViewRequest> {
+        ctx.data_mut(|d| d.remove_temp::<Vec<ViewRequest>>(Self::requests_id(id)))
+            .unwrap_or_default()
+    }
+
+    fn requests_id(id: Id) -> Id {
+        id.with("snarl-view-requests")
+    }
+}
@@ -11,21 +11,262 @@ pub enum WireLayer {
     AboveNodes,
 }
 
-/// Returns 6th degree bezier curve for the wire
-fn wire_bezier(
-    mut frame_size: f32,
-    upscale: bool,
-    downscale: bool,
-    from: Pos2,
-    to: Pos2,
-) -> [Pos2; 6] {
-    if upscale {
-        frame_size = frame_size.max((from - to).length() / 4.0);
+/// Shape of wires between pins.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WireStyle {
+    /// Smooth bezier curve.
+    /// This is default.
+    #[default]
+    Bezier,
+
+    /// Horizontal and vertical segments with right-angle corners.
+    Orthogonal,
+
+    /// Straight line between pins.
+    Straight,
+
+    /// Horizontal segments at pins joined by diagonal segment.
+    Stepped,
+}
+
+/// Color of a wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireColor {
+    /// Single color along the whole wire.
+    Solid(Color32),
+
+    /// Color changes gradually from output pin to input pin.
+    Gradient {
+        /// Color at the output pin.
+        from: Color32,
+
+        /// Color at the input pin.
+        to: Color32,
+    },
+}
+
+impl WireColor {
+    /// Returns color at the point `t` of the wire, from 0.0 at output pin to 1.0 at input pin.
+    pub(super) fn at(self, t: f32) -> Color32 {
+        match self {
+            WireColor::Solid(color) => color,
+            WireColor::Gradient { from, to } => lerp_colors(from, to, t),
+        }
+    }
+
+    fn gamma_multiply(self, factor: f32) -> Self {
+        match self {
+            WireColor::Solid(color) => WireColor::Solid(color.gamma_multiply(factor)),
+            WireColor::Gradient { from, to } => WireColor::Gradient {
+                from: from.gamma_multiply(factor),
+                to: to.gamma_multiply(factor),
+            },
+        }
+    }
+}
+
+impl From<Color32> for WireColor {
+    #[inline]
+    fn from(color: Color32) -> Self {
+        WireColor::Solid(color)
+    }
+}
+
+/// Information about a wire returned by `SnarlViewer::wire_info`.
+///
+/// Fields that are `None` are taken from [`SnarlStyle`](super::SnarlStyle)
+/// and colors of connected pins.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WireInfo {
+    /// Color of the wire.
+    /// Defaults to mix of fill colors of connected pins.
+    pub color: Option<WireColor>,
+
+    /// Width of the wire.
+    /// Defaults to [`SnarlStyle::wire_width`](super::SnarlStyle::wire_width).
+    pub width: Option<f32>,
+
+    /// Lengths of dashes and gaps between them.
+    /// `None` draws solid wire.
+    pub dash: Option<(f32, f32)>,
+
+    /// Layer where the wire is rendered.
+    /// Defaults to [`SnarlStyle::wire_layer`](super::SnarlStyle::wire_layer).
+    pub layer: Option<WireLayer>,
+
+    /// Animated pulses along the wire.
+    /// `None` draws static wire.
+    pub flow: Option<WireFlow>,
+}
+
+impl WireInfo {
+    /// Sets the color of the wire.
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = Some(WireColor::Solid(color));
+        self
+    }
+
+    /// Sets the gradient from output pin to input pin.
+    pub fn with_gradient(mut self, from: Color32, to: Color32) -> Self {
+        self.color = Some(WireColor::Gradient { from, to });
+        self
+    }
+
+    /// Sets the width of the wire.
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Makes the wire dashed.
+    pub fn with_dash(mut self, dash_length: f32, gap_length: f32) -> Self {
+        self.dash = Some((dash_length, gap_length));
+        self
+    }
+
+    /// Sets the layer where the wire is rendered.
+    pub fn with_layer(mut self, layer: WireLayer) -> Self {
+        self.layer = Some(layer);
+        self
+    }
+
+    /// Animates pulses along the wire.
+    pub fn with_flow(mut self, flow: WireFlow) -> Self {
+        self.flow = Some(flow);
+        self
+    }
+}
+
+/// Pulses moving along the wire to show data flowing through it.
+///
+/// Animation can be disabled for all wires with
+/// [`SnarlStyle::animate_wires`](super::SnarlStyle::animate_wires).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WireFlow {
+    /// Speed of pulses in graph units per second.
+    /// Pulses move from output pin to input pin, negative speed reverses them.
+    pub speed: f32,
+
+    /// Distance between pulses in graph units.
+    pub spacing: f32,
+
+    /// Color of pulses.
+    /// Defaults to color of the wire.
+    pub color: Option<Color32>,
+}
+
+impl Default for WireFlow {
+    fn default() -> Self {
+        WireFlow {
+            speed: 60.0,
+            spacing: 30.0,
+            color: None,
+        }
+    }
+}
+
+impl WireFlow {
+    /// Sets the speed of pulses.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
     }
-    if downscale {
-        frame_size = frame_size.min((from - to).length() / 4.0);
+
+    /// Sets the distance between pulses.
+    pub fn with_spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Sets the color of pulses.
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = Some(color);
+        self
     }
+}
 
+/// Resolved look of the wire in screen space.
+#[derive(Clone, Copy)]
+pub struct WirePaint {
+    pub width: f32,
+    pub color: WireColor,
+    pub dash: Option<(f32, f32)>,
+    pub flow: Option<FlowPaint>,
+}
+
+/// Resolved pulses of the wire in screen space.
+#[derive(Clone, Copy)]
+pub struct FlowPaint {
+    /// Distance of the first pulse from output pin.
+    pub offset: f32,
+    pub spacing: f32,
+    pub radius: f32,
+    pub color: Option<Color32>,
+}
+
+impl WirePaint {
+    pub fn new(width: f32, color: impl Into<WireColor>) -> Self {
+        WirePaint {
+            width,
+            color: color.into(),
+            dash: None,
+            flow: None,
+        }
+    }
+
+    pub fn faded(self) -> Self {
+        WirePaint {
+            color: self.color.gamma_multiply(0.3),
+            ..self
+        }
+    }
+}
+
+/// Geometry of the wire.
+enum WirePath {
+    Bezier([Pos2; 6]),
+    Polyline(Vec<Pos2>),
+}
+
+impl WirePath {
+    fn new(
+        style: WireStyle,
+        mut frame_size: f32,
+        upscale: bool,
+        downscale: bool,
+        from: Pos2,
+        to: Pos2,
+    ) -> Self {
+        if upscale {
+            frame_size = frame_size.max((from - to).length() / 4.0);
+        }
+        if downscale {
+            frame_size = frame_size.min((from - to).length() / 4.0);
+        }
+
+        match style {
+            WireStyle::Bezier => WirePath::Bezier(wire_bezier(frame_size, from, to)),
+            WireStyle::Orthogonal => WirePath::Polyline(wire_orthogonal(frame_size, from, to)),
+            WireStyle::Straight => WirePath::Polyline(vec![from, to]),
+            WireStyle::Stepped => WirePath::Polyline(vec![
+                from,
+                pos2(from.x + frame_size, from.y),
+                pos2(to.x - frame_size, to.y),
+                to,
+            ]),
+        }
+    }
+
+    fn bounding_rect(&self) -> Rect {
+        match self {
+            WirePath::Bezier(points) => Rect::from_points(points),
+            WirePath::Polyline(points) => Rect::from_points(points),
+        }
+    }
+}
+
+/// Returns 6th degree bezier curve for the wire
+fn wire_bezier(frame_size: f32, from: Pos2, to: Pos2) -> [Pos2; 6] {
     let from_norm_x = frame_size;
     let from_2 = pos2(from.x + from_norm_x, from.y);
     let to_norm_x = -from_norm_x;
@@ -125,36 +366,112 @@ fn wire_bezier(
     }
 }
 
+/// Returns orthogonal polyline for the wire.
+///
+/// Wire leaves output pin to the right and enters input pin from the left.
+/// If input pin is behind the output pin, wire goes around between them.
+fn wire_orthogonal(frame_size: f32, from: Pos2, to: Pos2) -> Vec<Pos2> {
+    if to.x - from.x >= frame_size {
+        let middle_x = (from.x + to.x) / 2.0;
+        return vec![from, pos2(middle_x, from.y), pos2(middle_x, to.y), to];
+    }
+
+    let from_x = from.x + frame_size;
+    let to_x = to.x - frame_size;
+
+    // Pins on the same level are bypassed below.
+    let middle_y = if (to.y - from.y).abs() < frame_size * 2.0 {
+        from.y.max(to.y) + frame_size * 2.0
+    } else {
+        (from.y + to.y) / 2.0
+    };
+
+    vec![
+        from,
+        pos2(from_x, from.y),
+        pos2(from_x, middle_y),
+        pos2(to_x, middle_y),
+        pos2(to_x, to.y),
+        to,
+    ]
+}
+
+/// Returns paths of wire segments between consecutive points of the route.
+///
+/// Route starts at the output pin, goes through reroute points and ends at the input pin.
+/// Each reroute point acts as input pin for one segment and as output pin for the next one.
+fn route_paths(
+    style: WireStyle,
+    frame_size: f32,
+    upscale: bool,
+    downscale: bool,
+    route: &[Pos2],
+) -> impl Iterator<Item = WirePath> + '_ {
+    route
+        .windows(2)
+        .map(move |ends| WirePath::new(style, frame_size, upscale, downscale, ends[0], ends[1]))
+}
+
+/// Draws the wire along the route.
+/// Returns false if the wire is not visible.
 #[allow(clippy::too_many_arguments)]
 pub fn draw_wire(
     ui: &mut Ui,
     shapes: &mut Vec<Shape>,
+    style: WireStyle,
     frame_size: f32,
     upscale: bool,
     downscale: bool,
-    from: Pos2,
-    to: Pos2,
-    stroke: Stroke,
-) {
-    let points = wire_bezier(frame_size, upscale, downscale, from, to);
+    route: &[Pos2],
+    paint: WirePaint,
+) -> bool {
+    let paths = route_paths(style, frame_size, upscale, downscale, route).collect::<Vec<_>>();
 
-    let bb = Rect::from_points(&points);
-    if ui.is_rect_visible(bb) {
-        draw_bezier(shapes, &points, stroke);
+    let bounding_rect = paths
+        .iter()
+        .fold(Rect::NOTHING, |rect, path| rect.union(path.bounding_rect()));
+    if !ui.is_rect_visible(bounding_rect) {
+        return false;
     }
+
+    // Segments are joined into single path, so dashes and flow continue through reroute points.
+    let mut points = Vec::new();
+    for path in paths {
+        let segment = match path {
+            WirePath::Bezier(points) => bezier_path(&points, paint.width.max(1.0)),
+            WirePath::Polyline(points) => points,
+        };
+        let skip = usize::from(points.last() == segment.first());
+        points.extend(segment.into_iter().skip(skip));
+    }
+
+    match paint.flow {
+        None => draw_path(shapes, points, paint),
+        Some(flow) => {
+            draw_path(shapes, points.clone(), paint);
+            draw_flow(shapes, &points, paint.color, flow);
+        }
+    }
+    true
 }
 
+/// Checks if position is within `threshold` from the wire along the route.
+/// Returns index of the hit segment, which is also the index
+/// a reroute point at the position should be inserted at.
+#[allow(clippy::too_many_arguments)]
 pub fn hit_wire(
     pos: Pos2,
+    style: WireStyle,
     frame_size: f32,
     upscale: bool,
     downscale: bool,
-    from: Pos2,
-    to: Pos2,
+    route: &[Pos2],
     threshold: f32,
-) -> bool {
-    let points = wire_bezier(frame_size, upscale, downscale, from, to);
-    hit_bezier(pos, &points, threshold)
+) -> Option<usize> {
+    route_paths(style, frame_size, upscale, downscale, route).position(|path| match path {
+        WirePath::Bezier(points) => hit_bezier(pos, &points, threshold),
+        WirePath::Polyline(points) => hit_polyline(pos, &points, threshold),
+    })
 }
 
 fn bezier_reference_size(points: &[Pos2; 6]) -> f32 {
@@ -177,13 +494,9 @@ fn bezier_samples_number(points: &[Pos2; 6], threshold: f32) -> usize {
     ((reference_size / threshold).ceil().max(0.0) as usize).min(MAX_BEZIER_SAMPLES)
 }
 
-fn draw_bezier(shapes: &mut Vec<Shape>, points: &[Pos2; 6], mut stroke: Stroke) {
-    if stroke.width < 1.0 {
-        stroke.color = stroke.color.gamma_multiply(stroke.width);
-        stroke.width = 1.0;
-    }
-
-    let samples = bezier_samples_number(points, stroke.width);
+/// Samples bezier curve into polyline with segments about `threshold` long.
+fn bezier_path(points: &[Pos2; 6], threshold: f32) -> Vec<Pos2> {
+    let samples = bezier_samples_number(points, threshold);
 
     let mut path = Vec::new();
 
@@ -193,14 +506,110 @@ fn draw_bezier(shapes: &mut Vec<Shape>, points: &[Pos2; 6], mut stroke: Stroke)
         path.push(sample_bezier(points, t));
     }
 
-    let shape = Shape::Path(PathShape {
-        points: path,
-        closed: false,
-        fill: Color32::TRANSPARENT,
-        stroke,
-    });
+    path
+}
+
+/// Length of pieces gradient wires are split into.
+const GRADIENT_STEP: f32 = 8.0;
+
+fn draw_path(shapes: &mut Vec<Shape>, points: Vec<Pos2>, mut paint: WirePaint) {
+    if paint.width < 1.0 {
+        paint.color = paint.color.gamma_multiply(paint.width);
+        paint.width = 1.0;
+    }
+    if paint
+        .dash
+        .is_some_and(|(dash, gap)| dash <= 0.0 || gap <= 0.0)
+    {
+        paint.dash = None;
+    }
+
+    if let (WireColor::Solid(color), None) = (paint.color, paint.dash) {
+        shapes.push(Shape::Path(PathShape {
+            points,
+            closed: false,
+            fill: Color32::TRANSPARENT,
+            stroke: Stroke::new(paint.width, color),
+        }));
+        return;
+    }
+
+    let total: f32 = points.windows(2).map(|s| (s[1] - s[0]).length()).sum();
+    let gradient = matches!(paint.color, WireColor::Gradient { .. });
+
+    // Whether distance along the wire falls on a dash.
+    let on_dash = |distance: f32| match paint.dash {
+        None => true,
+        Some((dash, gap)) => distance % (dash + gap) < dash,
+    };
+
+    // Wire is split at dash ends and at gradient steps,
+    // each piece is drawn with its own color.
+    let mut travelled = 0.0;
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = (b - a).length();
+        if length <= 0.0 {
+            continue;
+        }
+        let dir = (b - a) / length;
+
+        let mut start = 0.0;
+        while start < length {
+            let distance = travelled + start;
+            let mut end = length;
+            if let Some((dash, gap)) = paint.dash {
+                let phase = distance % (dash + gap);
+                let boundary = if phase < dash { dash } else { dash + gap };
+                end = end.min(start + boundary - phase);
+            }
+            if gradient {
+                end = end.min(start + GRADIENT_STEP);
+            }
+            // Always make progress despite rounding.
+            end = end.max((start + 0.01).min(length));
+
+            if on_dash(distance) {
+                let t = if total > 0.0 {
+                    (distance + (end - start) / 2.0) / total
+                } else {
+                    0.0
+                };
+                shapes.push(Shape::line_segment(
+                    [a + dir * start, a + dir * end],
+                    Stroke::new(paint.width, paint.color.at(t)),
+                ));
+            }
+            start = end;
+        }
+        travelled += length;
+    }
+}
+
+fn draw_flow(shapes: &mut Vec<Shape>, points: &[Pos2], color: WireColor, flow: FlowPaint) {
+    if flow.spacing <= 0.0 {
+        return;
+    }
+
+    let total: f32 = points.windows(2).map(|s| (s[1] - s[0]).length()).sum();
+    let mut next = flow.offset.rem_euclid(flow.spacing);
+    let mut travelled = 0.0;
+
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = (b - a).length();
+        if length <= 0.0 {
+            continue;
+        }
 
-    shapes.push(shape);
+        while next <= travelled + length {
+            let pos = a.lerp(b, (next - travelled) / length);
+            let color = flow.color.unwrap_or_else(|| color.at(next / total));
+            shapes.push(Shape::circle_filled(pos, flow.radius, color));
+            next += flow.spacing;
+        }
+        travelled += length;
+    }
 }
 
 #[allow(clippy::let_and_return)]
@@ -308,6 +717,30 @@ fn hit_bezier(pos: Pos2, points: &[Pos2; 6], threshold: f32) -> bool {
     false
 }
 
+fn hit_polyline(pos: Pos2, points: &[Pos2], threshold: f32) -> bool {
+    points.windows(2).any(|segment| {
+        let [a, b] = [segment[0], segment[1]];
+        let ab = b - a;
+        let t = if ab.length_sq() > 0.0 {
+            ((pos - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (a + ab * t - pos).length() < threshold
+    })
+}
+
+fn lerp_colors(a: Color32, b: Color32, t: f32) -> Color32 {
+    let [ar, ag, ab, aa] = a.to_array();
+    let [br, bg, bb, ba] = b.to_array();
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    let lerp = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+
+    Color32::from_rgba_premultiplied(lerp(ar, br), lerp(ag, bg), lerp(ab, bb), lerp(aa, ba))
+}
+
 pub fn mix_colors(a: Color32, b: Color32) -> Color32 {
     let [or, og, ob, oa] = a.to_array();
     let [ir, ig, ib, ia] = b.to_array();